@@ -0,0 +1,107 @@
+// Compares running many instances of a game as separate emulators on one thread and as a
+// batch, on one thread and on every core. All three run the same frames with the same seeds
+// and keys, and end in the same state.
+// Run with `cargo bench --bench batch`.
+
+use chip8_core::{bench, BatchEmulator, Emulator};
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+const ROMS: &[&str] = &["BRIX", "INVADERS", "PONG", "TETRIS"];
+const INSTANCES: usize = 64;
+const FRAMES: u32 = 600;
+// Each way runs this many times, alternating, and the fastest run counts
+const RUNS: usize = 5;
+
+fn seed(instance: usize) -> u64 {
+    bench::SEED + instance as u64
+}
+
+// Each instance gets the key script from a different point, so they do not all play alike
+fn keys(instance: usize, frame: u32) -> [bool; chip8_core::NUM_KEYS] {
+    bench::keys(frame + instance as u32 * 7)
+}
+
+fn run_emulators(rom: &[u8]) -> (Duration, u64) {
+    let mut emus: Vec<Emulator> = (0..INSTANCES)
+        .map(|instance| {
+            let mut emu = Emulator::new();
+            emu.load_fonts();
+            emu.load_rom_bytes(rom).unwrap();
+            if let Some(info) = emu.rom_info() {
+                emu.apply_rom_info(info);
+            }
+            emu.set_seed(seed(instance));
+            emu
+        })
+        .collect();
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        for (instance, emu) in emus.iter_mut().enumerate() {
+            emu.set_keys(keys(instance, frame));
+            emu.run_frame().unwrap();
+        }
+    }
+    let elapsed = start.elapsed();
+    (elapsed, emus.iter().map(Emulator::instruction_count).sum())
+}
+
+fn run_batch(rom: &[u8], threads: usize) -> (Duration, u64) {
+    let mut batch = BatchEmulator::new(rom, INSTANCES).unwrap();
+    batch.set_threads(threads);
+    for instance in 0..INSTANCES {
+        batch.set_seed(instance, seed(instance));
+    }
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        for instance in 0..INSTANCES {
+            batch.set_keys(instance, keys(instance, frame));
+        }
+        batch.run_frame();
+    }
+    let elapsed = start.elapsed();
+    let count = (0..INSTANCES)
+        .map(|instance| batch.instruction_count(instance))
+        .sum();
+    (elapsed, count)
+}
+
+fn main() {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    println!(
+        "{} instances, {} frames each, batch on 1 and {} threads",
+        INSTANCES, FRAMES, threads
+    );
+    for name in ROMS {
+        let rom = fs::read(format!("{}/../roms/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+        let mut separate = Duration::MAX;
+        let mut single = Duration::MAX;
+        let mut parallel = Duration::MAX;
+        let mut count = 0;
+        for _ in 0..RUNS {
+            let (time, separate_count) = run_emulators(&rom);
+            separate = separate.min(time);
+            let (time, single_count) = run_batch(&rom, 1);
+            single = single.min(time);
+            let (time, parallel_count) = run_batch(&rom, threads);
+            parallel = parallel.min(time);
+            assert!(
+                separate_count == single_count && single_count == parallel_count,
+                "batch ran a different number of instructions"
+            );
+            count = separate_count;
+        }
+        let frames = (INSTANCES as u32 * FRAMES) as f64;
+        println!(
+            "{:<10} emulators: {:>7.0} frames/s   batch: {:>7.0} frames/s   batch, {} threads: {:>8.0} frames/s   ({:.1} M instructions)",
+            name,
+            frames / separate.as_secs_f64(),
+            frames / single.as_secs_f64(),
+            threads,
+            frames / parallel.as_secs_f64(),
+            count as f64 / 1e6
+        );
+    }
+}
@@ -0,0 +1,46 @@
+// Compares decoding every instruction as it is fetched with the decoded instruction cache
+// `step` uses. Run with `cargo bench --bench decode`.
+
+use chip8_core::Emulator;
+use std::time::{Duration, Instant};
+
+const ROMS: &[&str] = &["BRIX", "INVADERS", "PONG", "TETRIS"];
+const INSTRUCTIONS: usize = 2_000_000;
+
+fn emulator(rom: &str) -> Emulator {
+    let mut emu = Emulator::new();
+    emu.start_game(format!("{}/../roms/{}", env!("CARGO_MANIFEST_DIR"), rom))
+        .unwrap();
+    emu.set_seed(1);
+    emu
+}
+
+fn run(rom: &str, decode_cache: bool) -> Duration {
+    let mut emu = emulator(rom);
+    emu.set_decode_cache(decode_cache);
+    let start = Instant::now();
+    for _ in 0..INSTRUCTIONS {
+        // Waiting for a key would stop the game, so keep one pressed
+        emu.set_key(5, true);
+        emu.step().unwrap();
+        if emu.is_waiting_for_key() {
+            emu.soft_reset();
+        }
+    }
+    start.elapsed()
+}
+
+fn main() {
+    println!("{} instructions per ROM", INSTRUCTIONS);
+    for rom in ROMS {
+        let decoded = run(rom, false);
+        let cached = run(rom, true);
+        println!(
+            "{:<10} decode every time: {:>8.1} M/s   cached: {:>8.1} M/s   ({:.2}x)",
+            rom,
+            INSTRUCTIONS as f64 / decoded.as_secs_f64() / 1e6,
+            INSTRUCTIONS as f64 / cached.as_secs_f64() / 1e6,
+            decoded.as_secs_f64() / cached.as_secs_f64()
+        );
+    }
+}
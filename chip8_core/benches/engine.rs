@@ -0,0 +1,60 @@
+// Compares running frames with the interpreter and with the basic-block engine.
+// Run with `cargo bench --bench engine`.
+
+use chip8_core::{Emulator, Engine};
+use std::time::{Duration, Instant};
+
+const ROMS: &[&str] = &["BRIX", "INVADERS", "PONG", "TETRIS"];
+const FRAMES: usize = 5_000;
+const INSTRUCTIONS_PER_FRAME: u32 = 1000;
+// Each engine runs this many times, alternating, and the fastest run counts
+const RUNS: usize = 5;
+
+fn run(rom: &str, engine: Engine) -> (Duration, u64) {
+    let mut emu = Emulator::new();
+    emu.start_game(format!("{}/../roms/{}", env!("CARGO_MANIFEST_DIR"), rom))
+        .unwrap();
+    emu.set_seed(1);
+    emu.set_instructions_per_frame(INSTRUCTIONS_PER_FRAME);
+    emu.set_engine(engine);
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        // Waiting for a key would stop the game, so keep one pressed
+        emu.set_key(5, true);
+        emu.run_frame().unwrap();
+        if emu.is_waiting_for_key() {
+            emu.soft_reset();
+        }
+    }
+    (start.elapsed(), emu.instruction_count())
+}
+
+fn main() {
+    println!(
+        "{} frames of {} instructions per ROM",
+        FRAMES, INSTRUCTIONS_PER_FRAME
+    );
+    for rom in ROMS {
+        let mut interpreted = Duration::MAX;
+        let mut blocks = Duration::MAX;
+        let mut count = 0;
+        for _ in 0..RUNS {
+            let (time, interpreter_count) = run(rom, Engine::Interpreter);
+            interpreted = interpreted.min(time);
+            let (time, block_count) = run(rom, Engine::Blocks);
+            blocks = blocks.min(time);
+            assert_eq!(
+                interpreter_count, block_count,
+                "engines ran different instruction counts"
+            );
+            count = block_count;
+        }
+        println!(
+            "{:<10} interpreter: {:>8.1} M/s   blocks: {:>8.1} M/s   ({:.2}x)",
+            rom,
+            count as f64 / interpreted.as_secs_f64() / 1e6,
+            count as f64 / blocks.as_secs_f64() / 1e6,
+            interpreted.as_secs_f64() / blocks.as_secs_f64()
+        );
+    }
+}
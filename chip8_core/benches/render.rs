@@ -0,0 +1,46 @@
+// Compares redrawing the whole screen after every instruction with only converting the region
+// the core marked as dirty. Run with `cargo bench --bench render`.
+
+use chip8_core::Emulator;
+use std::time::{Duration, Instant};
+
+const ROM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../roms/IBM_Logo.ch8");
+const INSTRUCTIONS: usize = 200_000;
+
+fn run(redraw: impl Fn(&mut Emulator) -> usize) -> (Duration, usize) {
+    let mut emu = Emulator::new();
+    emu.load_fonts();
+    emu.load_rom(String::from(ROM)).unwrap();
+
+    let start = Instant::now();
+    let mut bytes_converted = 0;
+    for _ in 0..INSTRUCTIONS {
+        let instruction_code = emu.fetch_instruction();
+        let instruction = emu.decode_instruction(instruction_code);
+        emu.execute_instruction(instruction).unwrap();
+        bytes_converted += redraw(&mut emu);
+    }
+    (start.elapsed(), bytes_converted)
+}
+
+fn main() {
+    let (full_time, full_bytes) = run(|emu| emu.screen_to_rgb(1).len());
+    let (dirty_time, dirty_bytes) = run(|emu| match emu.take_dirty_region() {
+        Some(region) => emu.region_to_rgb(region).len(),
+        None => 0,
+    });
+
+    println!("{} instructions of {}", INSTRUCTIONS, ROM);
+    println!(
+        "full redraw:  {:>10.2?} ({} bytes uploaded)",
+        full_time, full_bytes
+    );
+    println!(
+        "dirty region: {:>10.2?} ({} bytes uploaded)",
+        dirty_time, dirty_bytes
+    );
+    println!(
+        "speedup:      {:>10.1}x",
+        full_time.as_secs_f64() / dirty_time.as_secs_f64()
+    );
+}
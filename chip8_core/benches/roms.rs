@@ -0,0 +1,81 @@
+// Runs every ROM in roms/ headless with the fixed seed and key script from `bench`, for both
+// engines, and reports speed and where the time goes. Each ROM gets a warm-up run and then
+// several samples, reported as mean and standard deviation.
+// Run with `cargo bench --bench roms`.
+
+use chip8_core::{bench, Emulator, Engine};
+use std::fs;
+
+const FRAMES: u32 = 600;
+const SAMPLES: usize = 10;
+
+// Mean and standard deviation
+fn summary(samples: &[f64]) -> (f64, f64) {
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let variance =
+        samples.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / samples.len() as f64;
+    (mean, variance.sqrt())
+}
+
+fn main() {
+    let dir = format!("{}/../roms", env!("CARGO_MANIFEST_DIR"));
+    let mut roms: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .collect();
+    roms.sort();
+
+    println!(
+        "{} frames, seed {}, {} samples per ROM",
+        FRAMES,
+        bench::SEED,
+        SAMPLES
+    );
+    println!(
+        "{:<12} {:<12} {:>18} {:>19} {:>7} {:>8} {:>7}",
+        "ROM", "engine", "instructions/s", "frames/s", "decode", "execute", "render"
+    );
+    for path in &roms {
+        let name = path.file_name().unwrap().to_string_lossy();
+        for engine in [Engine::Interpreter, Engine::Blocks] {
+            let mut emu = Emulator::new();
+            emu.start_game(path.to_string_lossy().into_owned()).unwrap();
+            emu.set_engine(engine);
+
+            // A game that crashes is reported up to the crash, not benchmarked
+            if let Err(e) = bench::run(&mut emu, FRAMES, bench::SEED) {
+                println!("{:<12} {:<12} {}", name, format!("{:?}", engine), e);
+                continue;
+            }
+            let reports: Vec<bench::Report> = (0..SAMPLES)
+                .map(|_| bench::run(&mut emu, FRAMES, bench::SEED).unwrap())
+                .collect();
+
+            let ips: Vec<f64> = reports
+                .iter()
+                .map(|r| r.instructions_per_second())
+                .collect();
+            let fps: Vec<f64> = reports.iter().map(|r| r.frames_per_second()).collect();
+            let share = |part: fn(&bench::Report) -> std::time::Duration| {
+                let part: f64 = reports.iter().map(|r| part(r).as_secs_f64()).sum();
+                let total: f64 = reports.iter().map(|r| r.elapsed().as_secs_f64()).sum();
+                100.0 * part / total
+            };
+            let (ips, ips_deviation) = summary(&ips);
+            let (fps, fps_deviation) = summary(&fps);
+            println!(
+                "{:<12} {:<12} {:>8.2}M ± {:>5.2}M {:>9.0} ± {:>7.0} {:>6.1}% {:>7.1}% {:>6.1}%",
+                name,
+                format!("{:?}", engine),
+                ips / 1e6,
+                ips_deviation / 1e6,
+                fps,
+                fps_deviation,
+                share(bench::Report::decode),
+                share(bench::Report::execute),
+                share(|r| r.render),
+            );
+        }
+    }
+}
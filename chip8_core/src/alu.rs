@@ -0,0 +1,150 @@
+// 8XYN arithmetic and logic
+// Results and flags are both worked out from VX and VY as they were before the instruction.
+// VX is written first and VF last, so when X is F the flag is what VF ends up holding, as on
+// the original interpreter
+
+use crate::{Instruction, Quirks};
+
+// The new value of VX and the value VF is set to, if the instruction sets it
+pub(crate) fn execute(instruction: Instruction, x: u8, y: u8, quirks: Quirks) -> (u8, Option<u8>) {
+    // The logic instructions leave VF alone unless the interpreter clobbered it
+    let logic_flag = quirks.logic_resets_vf.then_some(0);
+    let shift_source = if quirks.shift_uses_vy { y } else { x };
+
+    match instruction {
+        Instruction::Set(..) => (y, None),
+        Instruction::Or(..) => (x | y, logic_flag),
+        Instruction::And(..) => (x & y, logic_flag),
+        Instruction::Xor(..) => (x ^ y, logic_flag),
+
+        // VF is the carry
+        Instruction::AddRegisters(..) => {
+            let (result, carry) = x.overflowing_add(y);
+            (result, Some(carry as u8))
+        }
+
+        // VF is 1 when there is no borrow
+        Instruction::Subtract(..) => (x.wrapping_sub(y), Some((x >= y) as u8)),
+        Instruction::ReverseSubtract(..) => (y.wrapping_sub(x), Some((y >= x) as u8)),
+
+        // VF is the bit shifted out
+        Instruction::ShiftRight(..) => (shift_source >> 1, Some(shift_source & 1)),
+        Instruction::ShiftLeft(..) => (shift_source << 1, Some(shift_source >> 7)),
+
+        _ => unreachable!("{:?} is not an 8XYN instruction", instruction),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Emulator, Instruction, Quirks};
+
+    const VIP: Quirks = Quirks::CHIP8;
+    const SCHIP: Quirks = Quirks::SUPER_CHIP;
+
+    // (quirks, opcode, V1 V2 VF before, V1 V2 VF after)
+    #[rustfmt::skip]
+    const CASES: &[(Quirks, u16, [u8; 3], [u8; 3])] = &[
+        // 8XY0
+        (VIP,   0x8120, [0x05, 0x07, 0x09], [0x07, 0x07, 0x09]),
+        // 8XY1 / 8XY2 / 8XY3 - VF reset on the VIP, left alone otherwise
+        (VIP,   0x8121, [0x0C, 0x0A, 0x09], [0x0E, 0x0A, 0x00]),
+        (SCHIP, 0x8121, [0x0C, 0x0A, 0x09], [0x0E, 0x0A, 0x09]),
+        (VIP,   0x8122, [0x0C, 0x0A, 0x09], [0x08, 0x0A, 0x00]),
+        (SCHIP, 0x8122, [0x0C, 0x0A, 0x09], [0x08, 0x0A, 0x09]),
+        (VIP,   0x8123, [0x0C, 0x0A, 0x09], [0x06, 0x0A, 0x00]),
+        (SCHIP, 0x8123, [0x0C, 0x0A, 0x09], [0x06, 0x0A, 0x09]),
+        // 8XY4 - VF is the carry
+        (VIP,   0x8124, [0x10, 0x20, 0x09], [0x30, 0x20, 0x00]),
+        (VIP,   0x8124, [0xFF, 0x02, 0x09], [0x01, 0x02, 0x01]),
+        (VIP,   0x8124, [0x80, 0x80, 0x09], [0x00, 0x80, 0x01]),
+        // 8XY5 - VF is 1 without a borrow, including when equal
+        (VIP,   0x8125, [0x05, 0x03, 0x09], [0x02, 0x03, 0x01]),
+        (VIP,   0x8125, [0x03, 0x05, 0x09], [0xFE, 0x05, 0x00]),
+        (VIP,   0x8125, [0x05, 0x05, 0x09], [0x00, 0x05, 0x01]),
+        // 8XY7 - VY minus VX
+        (VIP,   0x8127, [0x03, 0x05, 0x09], [0x02, 0x05, 0x01]),
+        (VIP,   0x8127, [0x05, 0x03, 0x09], [0xFE, 0x03, 0x00]),
+        (VIP,   0x8127, [0x05, 0x05, 0x09], [0x00, 0x05, 0x01]),
+        // 8XY6 / 8XYE - VF is the bit shifted out, of VY on the VIP and VX otherwise
+        (VIP,   0x8126, [0xFF, 0x05, 0x09], [0x02, 0x05, 0x01]),
+        (VIP,   0x8126, [0xFF, 0x04, 0x09], [0x02, 0x04, 0x00]),
+        (SCHIP, 0x8126, [0x05, 0xFF, 0x09], [0x02, 0xFF, 0x01]),
+        (SCHIP, 0x8126, [0x04, 0xFF, 0x09], [0x02, 0xFF, 0x00]),
+        (VIP,   0x812E, [0x00, 0x81, 0x09], [0x02, 0x81, 0x01]),
+        (VIP,   0x812E, [0xFF, 0x41, 0x09], [0x82, 0x41, 0x00]),
+        (SCHIP, 0x812E, [0x81, 0x00, 0x09], [0x02, 0x00, 0x01]),
+        (SCHIP, 0x812E, [0x41, 0xFF, 0x09], [0x82, 0xFF, 0x00]),
+    ];
+
+    // X = F: the flag is written after the result, so VF ends up holding the flag
+    #[rustfmt::skip]
+    const VF_AS_X: &[(Quirks, u16, [u8; 3], [u8; 3])] = &[
+        (VIP,   0x8F24, [0x00, 0x02, 0xFF], [0x00, 0x02, 0x01]),
+        (VIP,   0x8F24, [0x00, 0x02, 0x10], [0x00, 0x02, 0x00]),
+        (VIP,   0x8F25, [0x00, 0x03, 0x05], [0x00, 0x03, 0x01]),
+        (VIP,   0x8F25, [0x00, 0x05, 0x03], [0x00, 0x05, 0x00]),
+        (VIP,   0x8F27, [0x00, 0x05, 0x03], [0x00, 0x05, 0x01]),
+        (VIP,   0x8F27, [0x00, 0x03, 0x05], [0x00, 0x03, 0x00]),
+        (SCHIP, 0x8F26, [0x00, 0x00, 0x03], [0x00, 0x00, 0x01]),
+        (SCHIP, 0x8F2E, [0x00, 0x00, 0x40], [0x00, 0x00, 0x00]),
+        (VIP,   0x8F21, [0x00, 0x0A, 0x0C], [0x00, 0x0A, 0x00]),
+        (SCHIP, 0x8F21, [0x00, 0x0A, 0x0C], [0x00, 0x0A, 0x0E]),
+        (VIP,   0x8F20, [0x00, 0x0A, 0x0C], [0x00, 0x0A, 0x0A]),
+    ];
+
+    // Y = F: the result is worked out from VF as it was before the instruction
+    #[rustfmt::skip]
+    const VF_AS_Y: &[(Quirks, u16, [u8; 3], [u8; 3])] = &[
+        (VIP,   0x81F4, [0xFF, 0x00, 0x01], [0x00, 0x00, 0x01]),
+        (VIP,   0x81F4, [0x10, 0x00, 0x01], [0x11, 0x00, 0x00]),
+        (VIP,   0x81F5, [0x05, 0x00, 0x03], [0x02, 0x00, 0x01]),
+        (VIP,   0x81F5, [0x03, 0x00, 0x05], [0xFE, 0x00, 0x00]),
+        (VIP,   0x81F7, [0x05, 0x00, 0x03], [0xFE, 0x00, 0x00]),
+        (VIP,   0x81F7, [0x03, 0x00, 0x05], [0x02, 0x00, 0x01]),
+        (VIP,   0x81F6, [0x00, 0x00, 0x03], [0x01, 0x00, 0x01]),
+        (VIP,   0x81FE, [0x00, 0x00, 0x81], [0x02, 0x00, 0x01]),
+        (VIP,   0x81F0, [0x00, 0x00, 0x07], [0x07, 0x00, 0x07]),
+    ];
+
+    fn run(quirks: Quirks, opcode: u16, before: [u8; 3]) -> [u8; 3] {
+        let mut emu = Emulator::new();
+        emu.set_quirks(quirks);
+        emu.set_v_register(0x1, before[0]);
+        emu.set_v_register(0x2, before[1]);
+        emu.set_v_register(0xF, before[2]);
+        emu.execute_instruction(Instruction::decode(opcode))
+            .unwrap();
+        let v = emu.v_registers();
+        [v[0x1], v[0x2], v[0xF]]
+    }
+
+    fn check(cases: &[(Quirks, u16, [u8; 3], [u8; 3])]) {
+        for &(quirks, opcode, before, after) in cases {
+            assert_eq!(
+                run(quirks, opcode, before),
+                after,
+                "{:04X} with V1 V2 VF = {:02X?}, shift_uses_vy {}, logic_resets_vf {}",
+                opcode,
+                before,
+                quirks.shift_uses_vy,
+                quirks.logic_resets_vf
+            );
+        }
+    }
+
+    #[test]
+    fn every_8xyn_instruction() {
+        check(CASES);
+    }
+
+    #[test]
+    fn flag_wins_when_vf_is_the_destination() {
+        check(VF_AS_X);
+    }
+
+    #[test]
+    fn vf_as_the_source_is_read_before_the_flag_is_written() {
+        check(VF_AS_Y);
+    }
+}
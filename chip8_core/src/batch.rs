@@ -0,0 +1,871 @@
+// Many copies of one game run side by side, e.g. for training agents on many environments
+// at once. Each field of the machine is kept in its own array with one entry per instance,
+// and every `run_frame` runs one frame of every instance, split between worker threads that
+// are started once and kept. Instructions run through the same `machine::execute` as in
+// `Emulator`
+// The ROM is loaded and decoded once and shared by all instances. An instance only decodes
+// for itself at addresses it has written to, so self-modifying code still works
+// Instances run exactly like separate `Emulator`s with the same quirks, instructions per frame
+// and seed, using instructions-per-frame timing and the flat memory map. There is no
+// tracing, recording or profiling
+
+use crate::{
+    machine::{self, Machine, Screen},
+    read_opcode, romdb, unseeded_rng, EmulatorError, Framebuffer, Instruction, Quirks,
+    RandomSource, RomError, RomInfo, DEFAULT_INSTRUCTIONS_PER_FRAME, FONTS, FONT_ADDR,
+    MAX_ROM_SIZE, MAX_STACK_DEPTH, NUM_KEYS, NUM_REGS, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH,
+    START_ADDR,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc},
+    thread,
+};
+
+// One bit per RAM address
+type WrittenBits = [u64; RAM_SIZE / 64];
+
+// RAM at power-on, with the fonts and the ROM, and the instruction at every address in it
+struct RomImage {
+    ram: [u8; RAM_SIZE],
+    decoded: Vec<Instruction>,
+    info: Option<&'static RomInfo>,
+}
+
+impl RomImage {
+    fn new(rom: &[u8]) -> Result<Self, RomError> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(RomError::TooBig { size: rom.len() });
+        }
+        let mut ram = [0; RAM_SIZE];
+        let font = FONT_ADDR as usize;
+        ram[font..font + FONTS.len()].copy_from_slice(&FONTS);
+        let start = START_ADDR as usize;
+        ram[start..start + rom.len()].copy_from_slice(rom);
+
+        // The last byte of RAM cannot start an instruction
+        let decoded = ram
+            .windows(2)
+            .map(|bytes| Instruction::decode(u16::from_be_bytes([bytes[0], bytes[1]])))
+            .collect();
+        Ok(Self {
+            ram,
+            decoded,
+            info: romdb::lookup(rom),
+        })
+    }
+}
+
+// Settings every instance runs with
+#[derive(Clone)]
+struct Settings {
+    image: Arc<RomImage>,
+    quirks: Quirks,
+    instructions_per_frame: u32,
+}
+
+// The state of a run of consecutive instances, one array per field
+#[derive(Clone, Default)]
+struct Lanes {
+    pc: Vec<u16>,
+    v_registers: Vec<[u8; NUM_REGS]>,
+    i_register: Vec<u16>,
+    stack_pointer: Vec<u16>,
+    stack: Vec<[u16; MAX_STACK_DEPTH]>,
+    ram: Vec<[u8; RAM_SIZE]>,
+    // Addresses written since the last reset, where the shared decoding cannot be used
+    written: Vec<WrittenBits>,
+    screen: Vec<Screen>,
+    screen_changes: Vec<u64>,
+    keys: Vec<[bool; NUM_KEYS]>,
+    delay_timer: Vec<u8>,
+    sound_timer: Vec<u8>,
+    waiting_for_key: Vec<(bool, u8)>,
+    rng: Vec<StdRng>,
+    instruction_count: Vec<u64>,
+    // What stopped the instance, if it crashed
+    fault: Vec<Option<EmulatorError>>,
+}
+
+impl Lanes {
+    fn new(count: usize, image: &RomImage) -> Self {
+        Self {
+            pc: vec![START_ADDR; count],
+            v_registers: vec![[0; NUM_REGS]; count],
+            i_register: vec![0; count],
+            stack_pointer: vec![0; count],
+            stack: vec![[0; MAX_STACK_DEPTH]; count],
+            ram: vec![image.ram; count],
+            written: vec![[0; RAM_SIZE / 64]; count],
+            screen: vec![[false; SCREEN_WIDTH * SCREEN_HEIGHT]; count],
+            screen_changes: vec![0; count],
+            keys: vec![[false; NUM_KEYS]; count],
+            delay_timer: vec![0; count],
+            sound_timer: vec![0; count],
+            waiting_for_key: vec![(false, 0); count],
+            rng: (0..count).map(|_| unseeded_rng()).collect(),
+            instruction_count: vec![0; count],
+            fault: vec![None; count],
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.pc.len()
+    }
+
+    // Moves the instances from `at` on into a new run
+    fn split_off(&mut self, at: usize) -> Lanes {
+        Lanes {
+            pc: self.pc.split_off(at),
+            v_registers: self.v_registers.split_off(at),
+            i_register: self.i_register.split_off(at),
+            stack_pointer: self.stack_pointer.split_off(at),
+            stack: self.stack.split_off(at),
+            ram: self.ram.split_off(at),
+            written: self.written.split_off(at),
+            screen: self.screen.split_off(at),
+            screen_changes: self.screen_changes.split_off(at),
+            keys: self.keys.split_off(at),
+            delay_timer: self.delay_timer.split_off(at),
+            sound_timer: self.sound_timer.split_off(at),
+            waiting_for_key: self.waiting_for_key.split_off(at),
+            rng: self.rng.split_off(at),
+            instruction_count: self.instruction_count.split_off(at),
+            fault: self.fault.split_off(at),
+        }
+    }
+
+    // Moves every instance of `other` onto the end of this run
+    fn append(&mut self, other: &mut Lanes) {
+        self.pc.append(&mut other.pc);
+        self.v_registers.append(&mut other.v_registers);
+        self.i_register.append(&mut other.i_register);
+        self.stack_pointer.append(&mut other.stack_pointer);
+        self.stack.append(&mut other.stack);
+        self.ram.append(&mut other.ram);
+        self.written.append(&mut other.written);
+        self.screen.append(&mut other.screen);
+        self.screen_changes.append(&mut other.screen_changes);
+        self.keys.append(&mut other.keys);
+        self.delay_timer.append(&mut other.delay_timer);
+        self.sound_timer.append(&mut other.sound_timer);
+        self.waiting_for_key.append(&mut other.waiting_for_key);
+        self.rng.append(&mut other.rng);
+        self.instruction_count.append(&mut other.instruction_count);
+        self.fault.append(&mut other.fault);
+    }
+
+    fn lane(&mut self, index: usize, quirks: Quirks) -> Lane<'_> {
+        Lane {
+            quirks,
+            pc: &mut self.pc[index],
+            v_registers: &mut self.v_registers[index],
+            i_register: &mut self.i_register[index],
+            stack_pointer: &mut self.stack_pointer[index],
+            stack: &mut self.stack[index],
+            ram: &mut self.ram[index],
+            written: &mut self.written[index],
+            screen: &mut self.screen[index],
+            screen_changes: &mut self.screen_changes[index],
+            keys: &self.keys[index],
+            delay_timer: &mut self.delay_timer[index],
+            sound_timer: &mut self.sound_timer[index],
+            waiting_for_key: &mut self.waiting_for_key[index],
+            rng: &mut self.rng[index],
+            instruction_count: &mut self.instruction_count[index],
+        }
+    }
+
+    // Runs one frame of every instance that has not crashed
+    // A panic only stops the instance it happened in, which is left faulted like any crash
+    fn run_frame(&mut self, settings: &Settings) {
+        let mut next = 0;
+        while next < self.len() {
+            // Set up once for the whole run rather than for every instance
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                while next < self.len() {
+                    let index = next;
+                    next += 1;
+                    if self.fault[index].is_none() {
+                        let lane = &mut self.lane(index, settings.quirks);
+                        if let Err(e) = lane.run_frame(settings) {
+                            self.fault[index] = Some(e);
+                        }
+                    }
+                }
+            }));
+            if result.is_err() {
+                // Instructions panic after the fetch has moved PC past them
+                let index = next - 1;
+                let pc = self.pc[index].wrapping_sub(2);
+                self.fault[index] = Some(EmulatorError::Panicked { pc });
+            }
+        }
+    }
+}
+
+// One instance, borrowed out of its run
+struct Lane<'a> {
+    quirks: Quirks,
+    pc: &'a mut u16,
+    v_registers: &'a mut [u8; NUM_REGS],
+    i_register: &'a mut u16,
+    stack_pointer: &'a mut u16,
+    stack: &'a mut [u16; MAX_STACK_DEPTH],
+    ram: &'a mut [u8; RAM_SIZE],
+    written: &'a mut WrittenBits,
+    screen: &'a mut Screen,
+    screen_changes: &'a mut u64,
+    keys: &'a [bool; NUM_KEYS],
+    delay_timer: &'a mut u8,
+    sound_timer: &'a mut u8,
+    waiting_for_key: &'a mut (bool, u8),
+    rng: &'a mut StdRng,
+    instruction_count: &'a mut u64,
+}
+
+impl Lane<'_> {
+    // Same as `Emulator::run_frame`
+    fn run_frame(&mut self, settings: &Settings) -> Result<(), EmulatorError> {
+        let mut executed = 0;
+        while executed < settings.instructions_per_frame {
+            let waiting = &mut *self.waiting_for_key;
+            if waiting.0 {
+                for (key, pressed) in self.keys.iter().enumerate() {
+                    if *pressed {
+                        self.v_registers[waiting.1 as usize] = key as u8;
+                        waiting.0 = false;
+                        break;
+                    }
+                }
+                if waiting.0 {
+                    break;
+                }
+            }
+            let vblank_wait = self.step(&settings.image)?;
+            executed += 1;
+            if vblank_wait {
+                break;
+            }
+        }
+
+        *self.sound_timer = self.sound_timer.saturating_sub(1);
+        *self.delay_timer = self.delay_timer.saturating_sub(1);
+        Ok(())
+    }
+
+    fn is_written(&self, address: usize) -> bool {
+        self.written[address / 64] & (1 << (address % 64)) != 0
+    }
+
+    // Runs the instruction at PC - true if the frame should end to wait for the vertical blank
+    fn step(&mut self, image: &RomImage) -> Result<bool, EmulatorError> {
+        let pc = *self.pc;
+        machine::check_fetch(pc)?;
+        let address = pc as usize;
+        let instruction = if self.is_written(address) || self.is_written(address + 1) {
+            Instruction::decode(read_opcode(self.ram, pc))
+        } else {
+            image.decoded[address]
+        };
+
+        *self.pc = pc + 2;
+        if let Err(e) = machine::execute(self, instruction) {
+            *self.pc = pc;
+            return Err(e);
+        }
+        *self.instruction_count += 1;
+        Ok(self.quirks.display_wait && matches!(instruction, Instruction::Draw(..)))
+    }
+}
+
+impl Machine for Lane<'_> {
+    fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    fn stack_depth(&self) -> usize {
+        self.quirks.stack_depth
+    }
+
+    fn pc(&self) -> u16 {
+        *self.pc
+    }
+
+    fn set_pc(&mut self, pc: u16) {
+        *self.pc = pc;
+    }
+
+    fn v_registers(&self) -> &[u8; NUM_REGS] {
+        self.v_registers
+    }
+
+    fn v_registers_mut(&mut self) -> &mut [u8; NUM_REGS] {
+        self.v_registers
+    }
+
+    fn i_register(&self) -> u16 {
+        *self.i_register
+    }
+
+    fn set_i_register(&mut self, value: u16) {
+        *self.i_register = value;
+    }
+
+    fn stack_pointer(&self) -> usize {
+        *self.stack_pointer as usize
+    }
+
+    fn push_return(&mut self, address: u16) {
+        self.stack[*self.stack_pointer as usize] = address;
+        *self.stack_pointer += 1;
+    }
+
+    fn pop_return(&mut self) -> u16 {
+        *self.stack_pointer -= 1;
+        self.stack[*self.stack_pointer as usize]
+    }
+
+    fn keys(&self) -> &[bool; NUM_KEYS] {
+        self.keys
+    }
+
+    fn delay_timer(&self) -> u8 {
+        *self.delay_timer
+    }
+
+    fn set_delay_timer(&mut self, value: u8) {
+        *self.delay_timer = value;
+    }
+
+    fn set_sound_timer(&mut self, value: u8) {
+        *self.sound_timer = value;
+    }
+
+    fn wait_for_key(&mut self, register: u8) {
+        *self.waiting_for_key = (true, register);
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        self.rng.random_byte()
+    }
+
+    fn memory(&self) -> &[u8; RAM_SIZE] {
+        self.ram
+    }
+
+    fn write_ram(&mut self, address: usize, bytes: &[u8]) {
+        self.ram[address..address + bytes.len()].copy_from_slice(bytes);
+        for address in address..address + bytes.len() {
+            self.written[address / 64] |= 1 << (address % 64);
+        }
+    }
+
+    fn screen_and_ram(&mut self) -> (&mut Screen, &[u8; RAM_SIZE]) {
+        (self.screen, self.ram)
+    }
+
+    fn count_screen_change(&mut self) {
+        *self.screen_changes += 1;
+    }
+}
+
+// A run of instances handed to a worker thread for one frame, and handed back when it is done
+struct Job {
+    run: usize,
+    lanes: Lanes,
+    settings: Settings,
+}
+
+// Threads kept for as long as the batch, so a frame does not pay for starting them
+struct Workers {
+    jobs: Vec<mpsc::Sender<Job>>,
+    done: mpsc::Receiver<Job>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl Workers {
+    fn new(count: usize) -> Self {
+        let (done_sender, done) = mpsc::channel();
+        let mut jobs = Vec::with_capacity(count);
+        let mut threads = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let done_sender = done_sender.clone();
+            threads.push(thread::spawn(move || {
+                for mut job in receiver {
+                    job.lanes.run_frame(&job.settings);
+                    if done_sender.send(job).is_err() {
+                        break;
+                    }
+                }
+            }));
+            jobs.push(sender);
+        }
+        Self {
+            jobs,
+            done,
+            threads,
+        }
+    }
+}
+
+impl Drop for Workers {
+    // Closing the job channels lets the threads finish
+    fn drop(&mut self) {
+        self.jobs.clear();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+pub struct BatchEmulator {
+    image: Arc<RomImage>,
+    quirks: Quirks,
+    instructions_per_frame: u32,
+    threads: usize,
+    count: usize,
+    // The instances split into one run per thread, all but the last `run_len` long
+    runs: Vec<Lanes>,
+    run_len: usize,
+    // Started on the first frame that needs them
+    workers: Option<Workers>,
+}
+
+// Cloning shares the decoded ROM with the original, but not its threads
+impl Clone for BatchEmulator {
+    fn clone(&self) -> Self {
+        Self {
+            image: self.image.clone(),
+            quirks: self.quirks,
+            instructions_per_frame: self.instructions_per_frame,
+            threads: self.threads,
+            count: self.count,
+            runs: self.runs.clone(),
+            run_len: self.run_len,
+            workers: None,
+        }
+    }
+}
+
+impl BatchEmulator {
+    // `count` instances of a ROM, set up with the ROM database's quirks and speed if it is a
+    // known game, and using as many threads as the machine has cores
+    pub fn new(rom: &[u8], count: usize) -> Result<Self, RomError> {
+        let image = RomImage::new(rom)?;
+        let lanes = Lanes::new(count, &image);
+        let mut batch = Self {
+            image: Arc::new(image),
+            quirks: Quirks::default(),
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            threads: 1,
+            count,
+            runs: vec![lanes],
+            run_len: count.max(1),
+            workers: None,
+        };
+        batch.set_threads(thread::available_parallelism().map_or(1, |n| n.get()));
+        if let Some(info) = batch.image.info {
+            batch.set_quirks(info.quirks);
+            batch.set_instructions_per_frame(info.instructions_per_frame);
+        }
+        Ok(batch)
+    }
+
+    // Number of instances
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Worker threads `run_frame` splits the instances between - 1 runs them all on the
+    // calling thread
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+        self.workers = None;
+
+        let mut lanes = Lanes::default();
+        for mut run in self.runs.drain(..) {
+            lanes.append(&mut run);
+        }
+        self.run_len = self.count.div_ceil(self.threads).max(1);
+        while lanes.len() > self.run_len {
+            let rest = lanes.split_off(self.run_len);
+            self.runs.push(lanes);
+            lanes = rest;
+        }
+        self.runs.push(lanes);
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    // The stack depth is kept between 1 and `MAX_STACK_DEPTH`
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = Quirks {
+            stack_depth: quirks.stack_depth.clamp(1, MAX_STACK_DEPTH),
+            ..quirks
+        };
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame.max(1);
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    // Database entry of the ROM - None if it is not a known game
+    pub fn rom_info(&self) -> Option<&'static RomInfo> {
+        self.image.info
+    }
+
+    // Runs one 60 Hz frame of every instance, like `Emulator::run_frame` on each
+    // An instance that crashes or panics stops where it crashed and is skipped until it is
+    // reset, see `fault`
+    pub fn run_frame(&mut self) {
+        let settings = Settings {
+            image: self.image.clone(),
+            quirks: self.quirks,
+            instructions_per_frame: self.instructions_per_frame,
+        };
+        let (first, rest) = self
+            .runs
+            .split_first_mut()
+            .expect("a batch always has a run");
+        if rest.is_empty() {
+            first.run_frame(&settings);
+            return;
+        }
+
+        // The calling thread runs the first run while the workers run the others
+        let workers = self.workers.get_or_insert_with(|| Workers::new(rest.len()));
+        for (run, (lanes, jobs)) in rest.iter_mut().zip(&workers.jobs).enumerate() {
+            let job = Job {
+                run,
+                lanes: mem::take(lanes),
+                settings: settings.clone(),
+            };
+            jobs.send(job).expect("batch worker thread stopped");
+        }
+        first.run_frame(&settings);
+        for _ in 0..rest.len() {
+            let job = workers.done.recv().expect("batch worker thread stopped");
+            rest[job.run] = job.lanes;
+        }
+    }
+
+    // The run an instance is in, and its index there
+    fn locate(&self, lane: usize) -> (&Lanes, usize) {
+        assert!(lane < self.count, "instance {} of {}", lane, self.count);
+        (&self.runs[lane / self.run_len], lane % self.run_len)
+    }
+
+    fn locate_mut(&mut self, lane: usize) -> (&mut Lanes, usize) {
+        assert!(lane < self.count, "instance {} of {}", lane, self.count);
+        (&mut self.runs[lane / self.run_len], lane % self.run_len)
+    }
+
+    // Restarts an instance from power-on, like `Emulator::reset` - its random numbers carry on
+    // unless it is seeded again
+    pub fn reset(&mut self, lane: usize) {
+        let ram = self.image.ram;
+        let (lanes, index) = self.locate_mut(lane);
+        lanes.pc[index] = START_ADDR;
+        lanes.v_registers[index] = [0; NUM_REGS];
+        lanes.i_register[index] = 0;
+        lanes.stack_pointer[index] = 0;
+        lanes.stack[index] = [0; MAX_STACK_DEPTH];
+        lanes.ram[index] = ram;
+        lanes.written[index] = [0; RAM_SIZE / 64];
+        if lanes.screen[index].contains(&true) {
+            lanes.screen[index] = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+            lanes.screen_changes[index] += 1;
+        }
+        lanes.keys[index] = [false; NUM_KEYS];
+        lanes.delay_timer[index] = 0;
+        lanes.sound_timer[index] = 0;
+        lanes.waiting_for_key[index] = (false, 0);
+        lanes.instruction_count[index] = 0;
+        lanes.fault[index] = None;
+    }
+
+    // Makes an instance's CXNN produce the same numbers as an `Emulator` with the same seed
+    pub fn set_seed(&mut self, lane: usize, seed: u64) {
+        let (lanes, index) = self.locate_mut(lane);
+        lanes.rng[index] = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn set_keys(&mut self, lane: usize, keys: [bool; NUM_KEYS]) {
+        let (lanes, index) = self.locate_mut(lane);
+        lanes.keys[index] = keys;
+    }
+
+    // What stopped an instance, if it crashed since its last reset
+    pub fn fault(&self, lane: usize) -> Option<&EmulatorError> {
+        let (lanes, index) = self.locate(lane);
+        lanes.fault[index].as_ref()
+    }
+
+    pub fn pc(&self, lane: usize) -> u16 {
+        let (lanes, index) = self.locate(lane);
+        lanes.pc[index]
+    }
+
+    pub fn v_registers(&self, lane: usize) -> &[u8; NUM_REGS] {
+        let (lanes, index) = self.locate(lane);
+        &lanes.v_registers[index]
+    }
+
+    pub fn i_register(&self, lane: usize) -> u16 {
+        let (lanes, index) = self.locate(lane);
+        lanes.i_register[index]
+    }
+
+    // Return addresses of the subroutines being run, outermost call first
+    pub fn call_stack(&self, lane: usize) -> &[u16] {
+        let (lanes, index) = self.locate(lane);
+        &lanes.stack[index][..lanes.stack_pointer[index] as usize]
+    }
+
+    pub fn delay_timer(&self, lane: usize) -> u8 {
+        let (lanes, index) = self.locate(lane);
+        lanes.delay_timer[index]
+    }
+
+    pub fn sound_timer(&self, lane: usize) -> u8 {
+        let (lanes, index) = self.locate(lane);
+        lanes.sound_timer[index]
+    }
+
+    pub fn sound_active(&self, lane: usize) -> bool {
+        self.sound_timer(lane) > 0
+    }
+
+    pub fn memory(&self, lane: usize) -> &[u8; RAM_SIZE] {
+        let (lanes, index) = self.locate(lane);
+        &lanes.ram[index]
+    }
+
+    pub fn screen(&self, lane: usize) -> &[bool; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        let (lanes, index) = self.locate(lane);
+        &lanes.screen[index]
+    }
+
+    pub fn framebuffer(&self, lane: usize) -> Framebuffer<'_> {
+        let (lanes, index) = self.locate(lane);
+        Framebuffer::new(
+            &lanes.screen[index],
+            SCREEN_WIDTH,
+            lanes.screen_changes[index],
+        )
+    }
+
+    // True while FX0A is waiting for a key press
+    pub fn is_waiting_for_key(&self, lane: usize) -> bool {
+        let (lanes, index) = self.locate(lane);
+        lanes.waiting_for_key[index].0
+    }
+
+    pub fn instruction_count(&self, lane: usize) -> u64 {
+        let (lanes, index) = self.locate(lane);
+        lanes.instruction_count[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bench, Emulator};
+    use std::fs;
+
+    const LANES: usize = 7;
+
+    fn keys(lane: usize, frame: u32) -> [bool; NUM_KEYS] {
+        bench::keys(frame + lane as u32 * 7)
+    }
+
+    // Runs `frames` frames of the ROM as a batch and as separate emulators with the same seeds
+    // and keys, checking every instance after every frame
+    fn assert_batch_matches_emulators(
+        name: &str,
+        rom: &[u8],
+        quirks: Option<Quirks>,
+        threads: usize,
+        frames: u32,
+    ) {
+        let mut batch = BatchEmulator::new(rom, LANES).unwrap();
+        batch.set_threads(threads);
+        if let Some(quirks) = quirks {
+            batch.set_quirks(quirks);
+        }
+        let mut emus: Vec<Emulator> = (0..LANES)
+            .map(|lane| {
+                let mut emu = Emulator::new();
+                emu.load_fonts();
+                emu.load_rom_bytes(rom).unwrap();
+                if let Some(info) = emu.rom_info() {
+                    emu.apply_rom_info(info);
+                }
+                if let Some(quirks) = quirks {
+                    emu.set_quirks(quirks);
+                }
+                emu.set_seed(lane as u64);
+                batch.set_seed(lane, lane as u64);
+                emu
+            })
+            .collect();
+        let mut faults = [None; LANES];
+
+        for frame in 0..frames {
+            for (lane, emu) in emus.iter_mut().enumerate() {
+                emu.set_keys(keys(lane, frame));
+                batch.set_keys(lane, keys(lane, frame));
+                if faults[lane].is_none() {
+                    faults[lane] = emu.run_frame().err();
+                }
+            }
+            batch.run_frame();
+
+            for (lane, emu) in emus.iter().enumerate() {
+                let at = format!("{} {:?}, instance {}, frame {}", name, quirks, lane, frame);
+                assert_eq!(batch.fault(lane), faults[lane].as_ref(), "{}", at);
+                assert_eq!(batch.pc(lane), emu.pc(), "{}", at);
+                assert_eq!(batch.v_registers(lane), emu.v_registers(), "{}", at);
+                assert_eq!(batch.i_register(lane), emu.i_register(), "{}", at);
+                assert_eq!(batch.call_stack(lane), emu.call_stack(), "{}", at);
+                assert_eq!(batch.delay_timer(lane), emu.delay_timer(), "{}", at);
+                assert_eq!(batch.sound_timer(lane), emu.sound_timer(), "{}", at);
+                assert_eq!(batch.memory(lane), emu.memory(), "{}", at);
+                assert_eq!(batch.screen(lane), emu.screen(), "{}", at);
+                assert_eq!(
+                    batch.framebuffer(lane).change_count(),
+                    emu.framebuffer().change_count(),
+                    "{}",
+                    at
+                );
+                assert_eq!(
+                    batch.is_waiting_for_key(lane),
+                    emu.is_waiting_for_key(),
+                    "{}",
+                    at
+                );
+                assert_eq!(
+                    batch.instruction_count(lane),
+                    emu.instruction_count(),
+                    "{}",
+                    at
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn instances_run_like_separate_emulators() {
+        let roms = concat!(env!("CARGO_MANIFEST_DIR"), "/../roms");
+        let mut checked = 0;
+        for entry in fs::read_dir(roms).unwrap() {
+            let path = entry.unwrap().path();
+            if !path.is_file() {
+                continue;
+            }
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let rom = fs::read(&path).unwrap();
+            assert_batch_matches_emulators(&name, &rom, None, 1, 200);
+            assert_batch_matches_emulators(&name, &rom, Some(Quirks::CHIP8), 3, 200);
+            checked += 1;
+        }
+        assert!(checked > 0, "no ROMs found in {}", roms);
+    }
+
+    #[test]
+    fn self_modifying_code_runs_like_separate_emulators() {
+        // Counts V1 up and rewrites the 7201 at 20A so V2 goes up by the new V1 every loop
+        let rom = [
+            0x71, 0x01, // 200: V1 += 1
+            0xA2, 0x0B, // 202: I = 20B
+            0xF1, 0x55, // 204: store V0-V1 at 20B-20C
+            0x60, 0x72, // 206: V0 = 72
+            0x00, 0xE0, // 208: clear the screen, a no-op
+            0x72, 0x01, // 20A: V2 += NN, NN rewritten above
+            0x12, 0x00, // 20C: loop
+        ];
+        assert_batch_matches_emulators("self-modifying", &rom, None, 2, 60);
+    }
+
+    #[test]
+    fn roms_too_big_for_ram_are_rejected() {
+        let rom = [0; MAX_ROM_SIZE + 1];
+        assert_eq!(
+            BatchEmulator::new(&rom, 2).err(),
+            Emulator::new().load_rom_bytes(&rom).err()
+        );
+    }
+
+    #[test]
+    fn threads_can_change_between_frames() {
+        let rom = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/../roms/BRIX")).unwrap();
+        let mut single = BatchEmulator::new(&rom, LANES).unwrap();
+        single.set_threads(1);
+        let mut changing = single.clone();
+        for frame in 0..120 {
+            changing.set_threads(frame as usize % 5 + 1);
+            for lane in 0..LANES {
+                single.set_keys(lane, keys(lane, frame));
+                changing.set_keys(lane, keys(lane, frame));
+            }
+            single.run_frame();
+            changing.run_frame();
+        }
+        for lane in 0..LANES {
+            assert_eq!(changing.memory(lane), single.memory(lane));
+            assert_eq!(changing.screen(lane), single.screen(lane));
+            assert_eq!(
+                changing.instruction_count(lane),
+                single.instruction_count(lane)
+            );
+        }
+    }
+
+    #[test]
+    fn a_panicking_instance_only_stops_itself() {
+        // An endless loop of subroutine returns, each followed by a call back into it
+        let rom = [
+            0x22, 0x04, // 200: call 204
+            0x12, 0x00, // 202: loop
+            0x00, 0xEE, // 204: return
+        ];
+        let mut batch = BatchEmulator::new(&rom, 4).unwrap();
+        batch.set_threads(2);
+        // A stack pointer no instruction could leave, so the next return indexes past the stack
+        let (lanes, index) = batch.locate_mut(2);
+        lanes.stack_pointer[index] = MAX_STACK_DEPTH as u16 + 1;
+        lanes.pc[index] = 0x204;
+
+        for _ in 0..3 {
+            batch.run_frame();
+        }
+        assert_eq!(batch.fault(2), Some(&EmulatorError::Panicked { pc: 0x204 }));
+        for lane in [0, 1, 3] {
+            assert_eq!(batch.fault(lane), None);
+            assert_eq!(batch.instruction_count(lane), 30);
+        }
+
+        batch.reset(2);
+        batch.run_frame();
+        assert_eq!(batch.fault(2), None);
+        assert_eq!(batch.instruction_count(2), 10);
+    }
+}
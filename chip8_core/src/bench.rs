@@ -0,0 +1,111 @@
+// Headless benchmark runs
+// A ROM runs for a fixed number of frames with a fixed seed and key script, so runs can be
+// compared before and after a change. The speed comes from a plain run; a second, profiled run
+// of the same frames splits the emulation time between decoding and executing
+
+use crate::{Emulator, EmulatorError, NUM_KEYS};
+use std::{
+    hint,
+    time::{Duration, Instant},
+};
+
+// Seed for CXNN used unless another one is asked for
+pub const SEED: u64 = 1;
+
+// Time spent in each part of `step`, collected while profiling is on
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Profile {
+    // Fetching and decoding, including looking up cached instructions and blocks
+    pub decode: Duration,
+    pub execute: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Report {
+    pub frames: u32,
+    pub instructions: u64,
+
+    // Running the frames, and turning the screen into RGB after each one like a frontend would
+    pub emulate: Duration,
+    pub render: Duration,
+
+    // Share of the emulation time spent decoding, from the profiled run
+    pub decode_share: f64,
+}
+
+impl Report {
+    pub fn elapsed(&self) -> Duration {
+        self.emulate + self.render
+    }
+
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed().as_secs_f64()
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.elapsed().as_secs_f64()
+    }
+
+    pub fn decode(&self) -> Duration {
+        self.emulate.mul_f64(self.decode_share)
+    }
+
+    pub fn execute(&self) -> Duration {
+        self.emulate - self.decode()
+    }
+}
+
+// Keys held on `frame` - each key in turn is held for 20 frames and let go for 10, so games
+// waiting for a key keep going
+pub fn keys(frame: u32) -> [bool; NUM_KEYS] {
+    let mut keys = [false; NUM_KEYS];
+    if frame % 30 < 20 {
+        keys[(frame / 30) as usize % NUM_KEYS] = true;
+    }
+    keys
+}
+
+// Resets the emulator and runs `frames` frames twice, once timed and once profiled
+// Settings like quirks, timing and the engine are kept
+pub fn run(emu: &mut Emulator, frames: u32, seed: u64) -> Result<Report, EmulatorError> {
+    emu.reset();
+    emu.set_seed(seed);
+    let mut render = Duration::ZERO;
+    let start = Instant::now();
+    for frame in 0..frames {
+        emu.set_keys(keys(frame));
+        emu.run_frame()?;
+        let render_start = Instant::now();
+        if let Some(region) = emu.take_dirty_region() {
+            hint::black_box(emu.region_to_rgb(region));
+        }
+        render += render_start.elapsed();
+    }
+    let emulate = start.elapsed() - render;
+    let instructions = emu.instruction_count();
+
+    emu.reset();
+    emu.set_seed(seed);
+    emu.set_profiling(true);
+    let profiled = (0..frames).try_for_each(|frame| {
+        emu.set_keys(keys(frame));
+        emu.run_frame()
+    });
+    let profile = emu.profile().unwrap_or_default();
+    emu.set_profiling(false);
+    profiled?;
+
+    let profiled_time = profile.decode + profile.execute;
+    let decode_share = if profiled_time.is_zero() {
+        0.0
+    } else {
+        profile.decode.as_secs_f64() / profiled_time.as_secs_f64()
+    };
+    Ok(Report {
+        frames,
+        instructions,
+        emulate,
+        render,
+        decode_share,
+    })
+}
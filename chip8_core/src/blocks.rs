@@ -0,0 +1,322 @@
+// Basic-block execution engine
+// Straight runs of instructions are decoded once into blocks, which are then run without
+// fetching or decoding anything. A block ends after a jump, call, return or key wait, and is
+// left early when a skip is taken
+// Blocks are dropped when a byte they were decoded from is written, so self-modifying code
+// still works
+// Simple instructions - register moves, arithmetic, timers, jumps and skips - are compiled to
+// closures with their operands baked in. Those only run when nothing needs to see each
+// instruction go by: no tracer, instructions-per-frame timing and the flat memory map.
+// Everything else goes through the same path as the interpreter, so results are the same
+
+use crate::{alu, Emulator, Instruction, RAM_SIZE};
+use alloc::{boxed::Box, rc::Rc, vec, vec::Vec};
+use core::ops::Range;
+
+// Longest block decoded in one go - longer runs are split into several blocks
+const MAX_BLOCK_LENGTH: usize = 32;
+
+// Runs a compiled instruction - PC has already moved on to the next instruction
+type Compiled<R> = Box<dyn Fn(&mut Emulator<R>)>;
+
+// One decoded instruction of a block
+pub(crate) struct Op<R> {
+    pub pc: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    pub compiled: Option<Compiled<R>>,
+}
+
+pub(crate) struct BlockCache<R> {
+    // Blocks by start address, allocated the first time a block is decoded
+    blocks: Vec<Option<Rc<[Op<R>]>>>,
+
+    // How many blocks were decoded from each byte of RAM
+    coverage: Vec<u8>,
+
+    // Goes up whenever a block is dropped, so a running block can tell it was overwritten
+    generation: u64,
+}
+
+impl<R> BlockCache<R> {
+    pub fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            coverage: Vec::new(),
+            generation: 0,
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // The block starting at `pc`, decoded from `ram` if it is not cached yet
+    pub fn get(&mut self, pc: u16, ram: &[u8; RAM_SIZE]) -> Rc<[Op<R>]> {
+        if self.blocks.is_empty() {
+            self.blocks = vec![None; RAM_SIZE];
+            self.coverage = vec![0; RAM_SIZE];
+        }
+        if let Some(block) = &self.blocks[pc as usize] {
+            return Rc::clone(block);
+        }
+
+        let block = decode_block(pc, ram);
+        let end = block_end(&block);
+        for count in &mut self.coverage[pc as usize..end] {
+            *count += 1;
+        }
+        self.blocks[pc as usize] = Some(Rc::clone(&block));
+        block
+    }
+
+    // Drops the blocks decoded from any of the bytes in `written`
+    pub fn forget(&mut self, written: Range<usize>) {
+        let end = written.end.min(RAM_SIZE);
+        if self.blocks.is_empty()
+            || written.start >= end
+            || !self.coverage[written.start..end].iter().any(|&n| n > 0)
+        {
+            return;
+        }
+
+        // A block overlapping the write starts no further back than the longest block
+        let first = written.start.saturating_sub(2 * MAX_BLOCK_LENGTH);
+        for start in first..end {
+            let Some(block) = &self.blocks[start] else {
+                continue;
+            };
+            let block_end = block_end(block);
+            if block_end > written.start {
+                for count in &mut self.coverage[start..block_end] {
+                    *count -= 1;
+                }
+                self.blocks[start] = None;
+                self.generation += 1;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        if !self.blocks.is_empty() {
+            self.blocks.fill(None);
+            self.coverage.fill(0);
+            self.generation += 1;
+        }
+    }
+}
+
+// Decodes instructions from `pc` up to and including the first one that ends a block
+fn decode_block<R>(pc: u16, ram: &[u8; RAM_SIZE]) -> Rc<[Op<R>]> {
+    let mut ops = Vec::new();
+    let mut address = pc as usize;
+    // An instruction in the last byte of RAM would wrap around, leave it to the interpreter
+    while address + 1 < RAM_SIZE && ops.len() < MAX_BLOCK_LENGTH {
+        let opcode = (ram[address] as u16) << 8 | ram[address + 1] as u16;
+        let instruction = Instruction::decode(opcode);
+        ops.push(Op {
+            pc: address as u16,
+            opcode,
+            instruction,
+            compiled: compile(instruction),
+        });
+        if ends_block(instruction) {
+            break;
+        }
+        address += 2;
+    }
+    ops.into()
+}
+
+// First byte after the block
+fn block_end<R>(block: &[Op<R>]) -> usize {
+    block.last().map_or(0, |op| op.pc as usize + 2)
+}
+
+fn ends_block(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Jump(_)
+            | Instruction::CallSubRoutine(_)
+            | Instruction::SubroutineReturn
+            | Instruction::JumpWithOffset(_)
+            | Instruction::GetKey(_)
+    )
+}
+
+// A closure doing exactly what `execute_instruction` does for `instruction`, for the
+// instructions that cannot fault, write RAM or touch the screen
+fn compile<R>(instruction: Instruction) -> Option<Compiled<R>> {
+    Some(match instruction {
+        Instruction::Jump(nnn) => Box::new(move |emu| emu.pc = nnn),
+        Instruction::SkipIfEqual(vx, nn) => Box::new(move |emu| {
+            if emu.v_registers[vx as usize] == nn {
+                emu.pc += 2;
+            }
+        }),
+        Instruction::SkipIfNotEqual(vx, nn) => Box::new(move |emu| {
+            if emu.v_registers[vx as usize] != nn {
+                emu.pc += 2;
+            }
+        }),
+        Instruction::SkipIfRegistersEqual(vx, vy) => Box::new(move |emu| {
+            if emu.v_registers[vx as usize] == emu.v_registers[vy as usize] {
+                emu.pc += 2;
+            }
+        }),
+        Instruction::SkipIfRegistersNotEqual(vx, vy) => Box::new(move |emu| {
+            if emu.v_registers[vx as usize] != emu.v_registers[vy as usize] {
+                emu.pc += 2;
+            }
+        }),
+        Instruction::SkipIfKey(vx) => Box::new(move |emu| {
+            if emu.keys[(emu.v_registers[vx as usize] & 0xF) as usize] {
+                emu.pc += 2;
+            }
+        }),
+        Instruction::SkipIfNotKey(vx) => Box::new(move |emu| {
+            if !emu.keys[(emu.v_registers[vx as usize] & 0xF) as usize] {
+                emu.pc += 2;
+            }
+        }),
+        Instruction::SetRegister(vx, nn) => Box::new(move |emu| emu.v_registers[vx as usize] = nn),
+        Instruction::AddToRegister(vx, nn) => Box::new(move |emu| {
+            emu.v_registers[vx as usize] = emu.v_registers[vx as usize].wrapping_add(nn);
+        }),
+        Instruction::Set(vx, vy)
+        | Instruction::Or(vx, vy)
+        | Instruction::And(vx, vy)
+        | Instruction::Xor(vx, vy)
+        | Instruction::AddRegisters(vx, vy)
+        | Instruction::Subtract(vx, vy)
+        | Instruction::ReverseSubtract(vx, vy)
+        | Instruction::ShiftRight(vx, vy)
+        | Instruction::ShiftLeft(vx, vy) => Box::new(move |emu| {
+            let (result, flag) = alu::execute(
+                instruction,
+                emu.v_registers[vx as usize],
+                emu.v_registers[vy as usize],
+                emu.quirks,
+            );
+            emu.v_registers[vx as usize] = result;
+            if let Some(flag) = flag {
+                emu.v_registers[0xF] = flag;
+            }
+        }),
+        Instruction::SetIndexRegister(nnn) => Box::new(move |emu| emu.i_register = nnn),
+        Instruction::AddToIndex(vx) => Box::new(move |emu| {
+            emu.i_register = emu
+                .i_register
+                .wrapping_add(emu.v_registers[vx as usize] as u16)
+        }),
+        Instruction::GetTimer(vx) => {
+            Box::new(move |emu| emu.v_registers[vx as usize] = emu.delay_timer)
+        }
+        Instruction::SetDelayTimer(vx) => {
+            Box::new(move |emu| emu.delay_timer = emu.v_registers[vx as usize])
+        }
+        Instruction::SetSoundTimer(vx) => {
+            Box::new(move |emu| emu.sound_timer = emu.v_registers[vx as usize])
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Emulator, Engine, MemoryMap, Quirks, Timing, STATE_SIZE};
+    use std::fs;
+
+    fn start(rom: &[u8], engine: Engine, quirks: Quirks, vip: bool) -> Emulator {
+        let mut emu = Emulator::new();
+        emu.set_seed(7);
+        emu.load_fonts();
+        emu.load_rom_bytes(rom).unwrap();
+        emu.set_quirks(quirks);
+        if vip {
+            emu.set_memory_map(MemoryMap::Vip);
+            emu.set_timing(Timing::Vip);
+        }
+        emu.set_engine(engine);
+        emu
+    }
+
+    fn state(emu: &Emulator) -> Box<[u8; STATE_SIZE]> {
+        let mut state = Box::new([0; STATE_SIZE]);
+        emu.save_state(&mut state);
+        state
+    }
+
+    // Runs `rom` on both engines with the same seed and key presses, comparing registers,
+    // stack, timers, RAM and screen after every frame
+    fn assert_engines_agree(name: &str, rom: &[u8], frames: u32) {
+        let presets = [
+            Quirks::default(),
+            Quirks::CHIP8,
+            Quirks::SUPER_CHIP,
+            Quirks::XO_CHIP,
+        ];
+        for quirks in presets {
+            for vip in [false, true] {
+                let mut interpreter = start(rom, Engine::Interpreter, quirks, vip);
+                let mut blocks = start(rom, Engine::Blocks, quirks, vip);
+                for frame in 0..frames {
+                    let key = (frame / 20 % 16) as usize;
+                    let pressed = frame % 20 < 10;
+                    interpreter.set_key(key, pressed);
+                    blocks.set_key(key, pressed);
+
+                    let expected = interpreter.run_frame();
+                    let actual = blocks.run_frame();
+                    let context = format!("{} frame {} {:?} vip {}", name, frame, quirks, vip);
+                    assert_eq!(actual, expected, "{}", context);
+                    assert!(state(&blocks) == state(&interpreter), "{}", context);
+                    if expected.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bundled_roms_run_the_same_on_both_engines() {
+        let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/../roms");
+        let mut roms = 0;
+        for entry in fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_file() {
+                let rom = fs::read(&path).unwrap();
+                assert_engines_agree(&path.display().to_string(), &rom, 300);
+                roms += 1;
+            }
+        }
+        assert!(roms > 0, "no ROMs found in {}", directory);
+    }
+
+    #[test]
+    fn self_modifying_code_runs_the_same_on_both_engines() {
+        // The loop at 202 rewrites the instruction at 20A, inside its own block, to 72NN with
+        // a bigger NN every time round - a stale block would keep adding the old NN, or fault
+        // on the 0000 that is there before the first write
+        let rom = [
+            0x60, 0x72, // 200: V0 = 72
+            0x71, 0x01, // 202: V1 += 1
+            0xA2, 0x0A, // 204: I = 20A
+            0xF1, 0x55, // 206: store V0 and V1 at 20A
+            0x63, 0x00, // 208: V3 = 0
+            0x00, 0x00, // 20A: replaced with 72NN, V2 += NN
+            0x12, 0x02, // 20C: jump to 202
+        ];
+        assert_engines_agree("self-modifying loop", &rom, 30);
+
+        let mut emu = start(&rom, Engine::Blocks, Quirks::default(), false);
+        for _ in 0..30 {
+            emu.run_frame().unwrap();
+        }
+        // 50 times round the loop adds 1 + 2 + ... + 50
+        let loops = emu.v_registers()[1] as u32;
+        assert_eq!(loops, 50);
+        assert_eq!(emu.v_registers()[2] as u32, (loops * (loops + 1) / 2) % 256);
+    }
+}
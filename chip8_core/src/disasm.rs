@@ -0,0 +1,72 @@
+// Disassembler - turns instructions back into the usual CHIP-8 assembly mnemonics
+
+use crate::Instruction;
+use core::fmt;
+#[cfg(feature = "alloc")]
+use {crate::START_ADDR, alloc::vec::Vec};
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::SubroutineReturn => write!(f, "RET"),
+            Instruction::Jump(nnn) => write!(f, "JP   {:03X}", nnn),
+            Instruction::CallSubRoutine(nnn) => write!(f, "CALL {:03X}", nnn),
+
+            Instruction::SkipIfEqual(vx, nn) => write!(f, "SE   V{:X}, {:02X}", vx, nn),
+            Instruction::SkipIfNotEqual(vx, nn) => write!(f, "SNE  V{:X}, {:02X}", vx, nn),
+            Instruction::SkipIfRegistersEqual(vx, vy) => write!(f, "SE   V{:X}, V{:X}", vx, vy),
+            Instruction::SkipIfRegistersNotEqual(vx, vy) => {
+                write!(f, "SNE  V{:X}, V{:X}", vx, vy)
+            }
+
+            Instruction::SetRegister(vx, nn) => write!(f, "LD   V{:X}, {:02X}", vx, nn),
+            Instruction::AddToRegister(vx, nn) => write!(f, "ADD  V{:X}, {:02X}", vx, nn),
+
+            Instruction::Set(vx, vy) => write!(f, "LD   V{:X}, V{:X}", vx, vy),
+            Instruction::Or(vx, vy) => write!(f, "OR   V{:X}, V{:X}", vx, vy),
+            Instruction::And(vx, vy) => write!(f, "AND  V{:X}, V{:X}", vx, vy),
+            Instruction::Xor(vx, vy) => write!(f, "XOR  V{:X}, V{:X}", vx, vy),
+            Instruction::AddRegisters(vx, vy) => write!(f, "ADD  V{:X}, V{:X}", vx, vy),
+            Instruction::Subtract(vx, vy) => write!(f, "SUB  V{:X}, V{:X}", vx, vy),
+            Instruction::ReverseSubtract(vx, vy) => write!(f, "SUBN V{:X}, V{:X}", vx, vy),
+            Instruction::ShiftRight(vx, vy) => write!(f, "SHR  V{:X}, V{:X}", vx, vy),
+            Instruction::ShiftLeft(vx, vy) => write!(f, "SHL  V{:X}, V{:X}", vx, vy),
+
+            Instruction::SetIndexRegister(nnn) => write!(f, "LD   I, {:03X}", nnn),
+            Instruction::JumpWithOffset(nnn) => write!(f, "JP   V0, {:03X}", nnn),
+            Instruction::Random(vx, nn) => write!(f, "RND  V{:X}, {:02X}", vx, nn),
+
+            Instruction::Draw(vx, vy, n) => write!(f, "DRW  V{:X}, V{:X}, {:X}", vx, vy, n),
+            Instruction::SkipIfKey(vx) => write!(f, "SKP  V{:X}", vx),
+            Instruction::SkipIfNotKey(vx) => write!(f, "SKNP V{:X}", vx),
+            Instruction::GetKey(vx) => write!(f, "LD   V{:X}, K", vx),
+
+            Instruction::GetTimer(vx) => write!(f, "LD   V{:X}, DT", vx),
+            Instruction::SetDelayTimer(vx) => write!(f, "LD   DT, V{:X}", vx),
+            Instruction::SetSoundTimer(vx) => write!(f, "LD   ST, V{:X}", vx),
+
+            Instruction::AddToIndex(vx) => write!(f, "ADD  I, V{:X}", vx),
+            Instruction::FontCharacter(vx) => write!(f, "LD   F, V{:X}", vx),
+            Instruction::BinaryDecimalConversion(vx) => write!(f, "LD   B, V{:X}", vx),
+            Instruction::StoreMemory(vx) => write!(f, "LD   [I], V{:X}", vx),
+            Instruction::LoadMemory(vx) => write!(f, "LD   V{:X}, [I]", vx),
+
+            Instruction::PlaceHolder => write!(f, "???"),
+        }
+    }
+}
+
+// Decodes every two bytes of a ROM as an instruction - (address, opcode, instruction)
+// Data mixed in with the code is decoded too, so not every line is a real instruction
+#[cfg(feature = "alloc")]
+pub fn disassemble(rom: &[u8]) -> Vec<(u16, u16, Instruction)> {
+    rom.chunks(2)
+        .enumerate()
+        .map(|(i, bytes)| {
+            let opcode = ((bytes[0] as u16) << 8) | *bytes.get(1).unwrap_or(&0) as u16;
+            let address = START_ADDR + 2 * i as u16;
+            (address, opcode, Instruction::decode(opcode))
+        })
+        .collect()
+}
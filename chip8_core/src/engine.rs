@@ -0,0 +1,24 @@
+// Which engine runs frames - the block engine lives in `blocks` and needs the `alloc` feature;
+// without it `Engine::Blocks` runs frames through the interpreter
+
+use crate::ascii_lowercase;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    // Fetches and decodes every instruction as it runs
+    #[default]
+    Interpreter,
+    // Runs cached basic blocks
+    Blocks,
+}
+
+impl Engine {
+    // Looks up an engine by the names used on the command line
+    pub fn from_name(name: &str) -> Option<Engine> {
+        match ascii_lowercase(name, &mut [0; 16]) {
+            "interpreter" | "interp" => Some(Engine::Interpreter),
+            "blocks" | "jit" => Some(Engine::Blocks),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,357 @@
+// Reinforcement-learning environment over the emulator
+// Each step holds the chosen keys for one or more frames and returns the screen as packed
+// bits, the reward earned meanwhile and whether the episode is over. Rewards come from a
+// per-game extractor that reads the score out of RAM
+
+use crate::{romdb, Emulator, EmulatorError, RomInfo, NUM_KEYS, RAM_SIZE};
+use std::io;
+
+// The screen packed one bit per pixel, see `Framebuffer::as_packed_bits`
+pub type Observation = Vec<u8>;
+
+// Works out rewards and the end of an episode from the emulator's state
+pub trait RewardExtractor {
+    // Called after every reset, before the first step
+    fn reset(&mut self, emu: &Emulator);
+
+    // Reward earned since the last call, called after every frame
+    fn reward(&mut self, emu: &Emulator) -> f32;
+
+    // Whether the game is over
+    fn is_done(&self, emu: &Emulator) -> bool {
+        false
+    }
+}
+
+// A score kept in RAM as decimal digits, one per byte with the most significant first - the
+// way FX33 stores a number. The reward is how much the score went up - scores only go up, so
+// a lower score means the digits wrapped round, e.g. a one digit score going from 9 to 0
+#[derive(Debug, Clone)]
+pub struct BcdScore {
+    address: u16,
+    digits: usize,
+    last: u32,
+}
+
+impl BcdScore {
+    // None if the digits run past the end of RAM, or there are more than a u32 can count
+    pub fn new(address: u16, digits: usize) -> Option<Self> {
+        if !(1..=9).contains(&digits) || address as usize + digits > RAM_SIZE {
+            return None;
+        }
+        Some(Self {
+            address,
+            digits,
+            last: 0,
+        })
+    }
+
+    pub fn read(&self, emu: &Emulator) -> u32 {
+        let start = self.address as usize;
+        emu.memory()[start..start + self.digits]
+            .iter()
+            .fold(0, |score, digit| score * 10 + *digit as u32)
+    }
+}
+
+impl RewardExtractor for BcdScore {
+    fn reset(&mut self, emu: &Emulator) {
+        self.last = self.read(emu);
+    }
+
+    fn reward(&mut self, emu: &Emulator) -> f32 {
+        let score = self.read(emu);
+        let wrap = 10u32.pow(self.digits as u32);
+        let reward = (score + wrap - self.last) % wrap;
+        self.last = score;
+        reward as f32
+    }
+}
+
+// Two-player games - points scored minus points conceded
+#[derive(Debug, Clone)]
+pub struct ScoreDifference {
+    pub own: BcdScore,
+    pub opponent: BcdScore,
+}
+
+impl RewardExtractor for ScoreDifference {
+    fn reset(&mut self, emu: &Emulator) {
+        self.own.reset(emu);
+        self.opponent.reset(emu);
+    }
+
+    fn reward(&mut self, emu: &Emulator) -> f32 {
+        self.own.reward(emu) - self.opponent.reward(emu)
+    }
+}
+
+// Two-player games keeping both scores in one byte, written out with FX33 - each player adds
+// their own step to it. Reading the whole byte keeps a carry into the next digit from counting
+// as a point for the other player, so `opponent_step` has to divide `own_step`
+#[derive(Debug, Clone)]
+pub struct SharedScore {
+    counter: BcdScore,
+    own_step: u8,
+    opponent_step: u8,
+}
+
+impl SharedScore {
+    // `address` is where FX33 writes the byte - None if that is past the end of RAM, or either
+    // step is 0
+    pub fn new(address: u16, own_step: u8, opponent_step: u8) -> Option<Self> {
+        if own_step == 0 || opponent_step == 0 {
+            return None;
+        }
+        Some(Self {
+            counter: BcdScore::new(address, 3)?,
+            own_step,
+            opponent_step,
+        })
+    }
+}
+
+impl RewardExtractor for SharedScore {
+    fn reset(&mut self, emu: &Emulator) {
+        self.counter.reset(emu);
+    }
+
+    fn reward(&mut self, emu: &Emulator) -> f32 {
+        let score = self.counter.read(emu);
+        // The byte wraps at 256, not at 1000 like three digits would
+        let change = (score as u8).wrapping_sub(self.counter.last as u8);
+        self.counter.last = score;
+        let own = change / self.own_step;
+        let opponent = change % self.own_step / self.opponent_step;
+        own as f32 - opponent as f32
+    }
+}
+
+// Reward extractor for a game from the ROM database, if its score's place in RAM is known
+// Matched by hash rather than title, so another ROM called Pong does not get these addresses
+pub fn reward_extractor(info: &RomInfo) -> Option<Box<dyn RewardExtractor>> {
+    match info.sha1 {
+        // Both add 10 to VE when the left paddle, the agent's, scores and 1 when the right
+        // one does, and write VE out with FX33 at 0x2F2
+        romdb::PONG_SHA1 | romdb::PONG_2_SHA1 => Some(Box::new(SharedScore::new(0x2F2, 10, 1)?)),
+        _ => None,
+    }
+}
+
+pub struct Chip8Env {
+    emu: Emulator,
+    reward: Option<Box<dyn RewardExtractor>>,
+
+    // Frames each step runs with the same keys held
+    frame_skip: u32,
+
+    // Episodes are cut off after this many frames, if set
+    max_frames: Option<u32>,
+
+    frames: u32,
+    done: bool,
+
+    // What ended the episode if the game crashed
+    fault: Option<EmulatorError>,
+}
+
+impl Chip8Env {
+    // Loads a ROM with the ROM database's settings and reward extractor, if it has them
+    pub fn new(rom: &[u8]) -> io::Result<Self> {
+        let mut emu = Emulator::new();
+        emu.load_fonts();
+        emu.load_rom_bytes(rom)?;
+        let info = romdb::lookup(rom);
+        if let Some(info) = info {
+            emu.apply_rom_info(info);
+        }
+        let mut env = Self::with_emulator(emu);
+        env.reward = info.and_then(reward_extractor);
+        Ok(env)
+    }
+
+    // Wraps an emulator that already has a ROM loaded and is set up as wanted
+    pub fn with_emulator(emu: Emulator) -> Self {
+        Self {
+            emu,
+            reward: None,
+            frame_skip: 1,
+            max_frames: None,
+            frames: 0,
+            done: false,
+            fault: None,
+        }
+    }
+
+    pub fn set_reward_extractor(&mut self, reward: Option<Box<dyn RewardExtractor>>) {
+        self.reward = reward;
+    }
+
+    pub fn set_frame_skip(&mut self, frame_skip: u32) {
+        self.frame_skip = frame_skip.max(1);
+    }
+
+    pub fn set_max_frames(&mut self, max_frames: Option<u32>) {
+        self.max_frames = max_frames;
+    }
+
+    pub fn emulator(&self) -> &Emulator {
+        &self.emu
+    }
+
+    // Frames run since the last reset
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn fault(&self) -> Option<&EmulatorError> {
+        self.fault.as_ref()
+    }
+
+    // Starts a new episode from power-on, with CXNN seeded by `seed`
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.emu.reset();
+        self.emu.set_seed(seed);
+        self.frames = 0;
+        self.done = false;
+        self.fault = None;
+        if let Some(reward) = &mut self.reward {
+            reward.reset(&self.emu);
+        }
+        self.observation()
+    }
+
+    // Holds `keys` for `frame_skip` frames - returns the screen, the reward summed over those
+    // frames and whether the episode ended. A crash ends the episode, see `fault`
+    // Once the episode is over nothing more runs until the next reset
+    pub fn step(&mut self, keys: [bool; NUM_KEYS]) -> (Observation, f32, bool) {
+        let mut total = 0.0;
+        for _ in 0..self.frame_skip {
+            if self.done {
+                break;
+            }
+            self.emu.set_keys(keys);
+            if let Err(e) = self.emu.run_frame() {
+                self.fault = Some(e);
+                self.done = true;
+                break;
+            }
+            self.frames += 1;
+            if let Some(reward) = &mut self.reward {
+                total += reward.reward(&self.emu);
+                self.done = reward.is_done(&self.emu);
+            }
+            if self.max_frames.is_some_and(|max| self.frames >= max) {
+                self.done = true;
+            }
+        }
+        (self.observation(), total, self.done)
+    }
+
+    pub fn observation(&self) -> Observation {
+        self.emu.framebuffer().as_packed_bits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::romdb::ROMS;
+    use std::fs;
+
+    fn pong() -> Vec<u8> {
+        fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/../roms/PONG")).unwrap()
+    }
+
+    #[test]
+    fn rewards_follow_a_scripted_pong_episode() {
+        let mut env = Chip8Env::new(&pong()).unwrap();
+        env.reset(3);
+        let mut rewards = Vec::new();
+        for frame in 0..12000 {
+            // Sweep the left paddle up and down
+            let mut keys = [false; NUM_KEYS];
+            keys[if frame / 90 % 2 == 0 { 0x1 } else { 0x4 }] = true;
+            let (_, reward, done) = env.step(keys);
+            assert!(!done);
+            if reward != 0.0 {
+                rewards.push(reward);
+            }
+        }
+        // VE goes 98 -> 108 and 189 -> 190 on the way, where a digit wraps round
+        #[rustfmt::skip]
+        let expected = [
+            1.0, 1.0, 1.0, 1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0, 1.0,
+            -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+            -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, 1.0,
+        ];
+        assert_eq!(rewards, expected);
+        assert_eq!(env.emulator().v_registers()[0xE], 205);
+    }
+
+    #[test]
+    fn shared_score_handles_carries_and_the_byte_wrapping() {
+        let mut emu = Emulator::new();
+        let mut score = SharedScore::new(0x300, 10, 1).unwrap();
+        score.reset(&emu);
+        for (digits, reward) in [
+            ([0, 0, 1], -1.0),
+            ([0, 0, 9], -8.0),
+            // The right player's tenth point carries into the tens digit
+            ([0, 1, 0], -1.0),
+            ([0, 2, 0], 1.0),
+            ([1, 0, 0], 8.0),
+            ([2, 5, 0], 15.0),
+            // 250 + 10 wraps round to 4
+            ([0, 0, 4], 1.0),
+        ] {
+            emu.write_memory(0x300, &digits);
+            assert_eq!(score.reward(&emu), reward, "score {:?}", digits);
+        }
+    }
+
+    #[test]
+    fn bcd_score_counts_a_wrapped_digit_as_a_point() {
+        let mut emu = Emulator::new();
+        emu.write_memory(0x300, &[9]);
+        let mut score = BcdScore::new(0x300, 1).unwrap();
+        score.reset(&emu);
+        emu.write_memory(0x300, &[0]);
+        assert_eq!(score.reward(&emu), 1.0);
+    }
+
+    #[test]
+    fn scores_that_cannot_be_read_are_rejected() {
+        let mut emu = Emulator::new();
+        emu.write_memory(0xFFE, &[4, 2]);
+        let mut score = BcdScore::new(0xFFE, 2).unwrap();
+        score.reset(&emu);
+        assert_eq!(score.read(&emu), 42);
+
+        assert!(BcdScore::new(0xFFE, 3).is_none());
+        assert!(BcdScore::new(0x300, 0).is_none());
+        assert!(BcdScore::new(0x300, 10).is_none());
+        assert!(SharedScore::new(0xFFE, 10, 1).is_none());
+        assert!(SharedScore::new(0x300, 0, 1).is_none());
+        assert!(SharedScore::new(0x300, 10, 0).is_none());
+    }
+
+    #[test]
+    fn reward_extractor_is_chosen_by_hash_not_title() {
+        for title in ["Pong", "Pong 2"] {
+            let info = ROMS.iter().find(|info| info.title == title).unwrap();
+            assert!(reward_extractor(info).is_some(), "{}", title);
+        }
+        let pong = ROMS.iter().find(|info| info.title == "Pong").unwrap();
+        let renamed = RomInfo {
+            title: "Something else",
+            ..*pong
+        };
+        assert!(reward_extractor(&renamed).is_some());
+        let other = RomInfo {
+            sha1: "0000000000000000000000000000000000000000",
+            ..*pong
+        };
+        assert!(reward_extractor(&other).is_none());
+    }
+}
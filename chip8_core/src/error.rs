@@ -0,0 +1,109 @@
+// Faults a running program can cause - the emulator stops on the faulting instruction
+
+use crate::MAX_ROM_SIZE;
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmulatorError {
+    // 2NNN called with every stack slot in use
+    StackOverflow { address: u16, depth: usize },
+
+    // 00EE returned with nothing on the stack
+    StackUnderflow { address: u16 },
+
+    // An opcode no instruction decodes to, e.g. data run as code or a 0NNN machine code call
+    UnknownOpcode { pc: u16, opcode: u16 },
+
+    // An instruction fetched, or RAM read or written from I, past the end of RAM
+    MemoryOutOfRange { pc: u16, address: u16 },
+
+    // A bug in the emulator - an instance of a `BatchEmulator` panicked running the instruction
+    // at `pc` and was stopped
+    Panicked { pc: u16 },
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmulatorError::StackOverflow { address, depth } => write!(
+                f,
+                "stack overflow at {:03X}: call nested deeper than {} levels",
+                address, depth
+            ),
+            EmulatorError::StackUnderflow { address } => {
+                write!(
+                    f,
+                    "stack underflow at {:03X}: return with an empty stack",
+                    address
+                )
+            }
+            EmulatorError::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode {:04X} at {:03X}", opcode, pc)
+            }
+            EmulatorError::MemoryOutOfRange { pc, address } => write!(
+                f,
+                "access to {:03X} by the instruction at {:03X} runs past the end of RAM",
+                address, pc
+            ),
+            EmulatorError::Panicked { pc } => write!(f, "emulator panicked at {:03X}", pc),
+        }
+    }
+}
+
+impl core::error::Error for EmulatorError {}
+
+// Reasons a saved state cannot be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    // Does not start with the state header
+    NotAState,
+
+    // Saved in a layout this version does not know
+    UnsupportedVersion(u8),
+
+    // Holds something no running machine could have, e.g. a stack deeper than the maximum
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::NotAState => write!(f, "not a saved state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "saved state version {} is not supported", version)
+            }
+            StateError::Invalid(reason) => write!(f, "invalid saved state: {}", reason),
+        }
+    }
+}
+
+impl core::error::Error for StateError {}
+
+// Reasons a ROM cannot be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomError {
+    // Does not fit in the RAM after 0x200
+    TooBig { size: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::TooBig { size } => write!(
+                f,
+                "ROM is {} bytes, the maximum is {} bytes",
+                size, MAX_ROM_SIZE
+            ),
+        }
+    }
+}
+
+impl core::error::Error for RomError {}
+
+// So file loading can keep returning `io::Result`
+#[cfg(feature = "std")]
+impl From<RomError> for std::io::Error {
+    fn from(error: RomError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
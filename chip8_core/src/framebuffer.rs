@@ -0,0 +1,106 @@
+// Read-only view of the display, so tools can render or hash frames without a window
+// Pixels are stored row by row from the top left corner
+
+#[cfg(feature = "alloc")]
+use {
+    crate::Palette,
+    alloc::{vec, vec::Vec},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Framebuffer<'a> {
+    pixels: &'a [bool],
+    width: usize,
+    height: usize,
+    changes: u64,
+}
+
+impl<'a> Framebuffer<'a> {
+    pub(crate) fn new(pixels: &'a [bool], width: usize, changes: u64) -> Self {
+        Self {
+            pixels,
+            width,
+            height: pixels.len() / width,
+            changes,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // (width, height) in CHIP-8 pixels
+    pub fn resolution(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    // Whether the pixel at (x, y) is lit - panics outside the screen
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel ({}, {}) is outside the {}x{} screen",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        self.pixels[y * self.width + x]
+    }
+
+    // The rows from top to bottom
+    pub fn rows(&self) -> impl Iterator<Item = &'a [bool]> {
+        self.pixels.chunks_exact(self.width)
+    }
+
+    // One bit per pixel, row by row, with the leftmost pixel in the top bit of each byte -
+    // 256 bytes for the 64x32 screen
+    // Rows that are not a whole number of bytes are padded with zero bits
+    #[cfg(feature = "alloc")]
+    pub fn as_packed_bits(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.packed_len()];
+        self.pack_bits_into(&mut bytes);
+        bytes
+    }
+
+    // Bytes `pack_bits_into` writes
+    pub fn packed_len(&self) -> usize {
+        self.width.div_ceil(8) * self.height
+    }
+
+    // `as_packed_bits` into a buffer of at least `packed_len` bytes, for displays driven
+    // without a heap - panics if the buffer is too small
+    pub fn pack_bits_into(&self, out: &mut [u8]) {
+        let chunks = self.rows().flat_map(|row| row.chunks(8));
+        for (byte, chunk) in out[..self.packed_len()].iter_mut().zip(chunks) {
+            *byte = chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (bit, lit)| byte | (*lit as u8) << (7 - bit));
+        }
+    }
+
+    // Red, green, blue and alpha bytes for every pixel, fully opaque
+    #[cfg(feature = "alloc")]
+    pub fn to_rgba(&self, palette: Palette) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in self.pixels {
+            let (r, g, b) = if *pixel {
+                palette.foreground
+            } else {
+                palette.background
+            };
+            rgba.extend_from_slice(&[r, g, b, 255]);
+        }
+        rgba
+    }
+
+    // Goes up every time the screen changes, so a frame only needs rendering or hashing when
+    // this differs from the last one seen
+    pub fn change_count(&self) -> u64 {
+        self.changes
+    }
+}
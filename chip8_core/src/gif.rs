@@ -0,0 +1,139 @@
+// A minimal animated GIF encoder for two-colour images
+// Frames are given as one palette index (0 or 1) per pixel, plus a delay in hundredths of a second
+
+use std::collections::HashMap;
+
+// GIF codes are at most 12 bits wide
+const MAX_CODES: u16 = 4096;
+
+// Two colours need a minimum LZW code size of 2 - GIF does not allow anything smaller
+const MIN_CODE_SIZE: u8 = 2;
+
+pub struct GifFrame {
+    pub pixels: Vec<u8>,
+    pub delay: u16,
+}
+
+// Encodes an endlessly looping animation using a two colour global palette
+pub fn encode_animation(
+    width: u16,
+    height: u16,
+    colours: [(u8, u8, u8); 2],
+    frames: &[GifFrame],
+) -> Vec<u8> {
+    let mut gif = Vec::new();
+    gif.extend_from_slice(b"GIF89a");
+
+    // Logical screen descriptor - global colour table present with 2 entries
+    gif.extend_from_slice(&width.to_le_bytes());
+    gif.extend_from_slice(&height.to_le_bytes());
+    gif.extend_from_slice(&[0x80, 0, 0]);
+    for (r, g, b) in colours {
+        gif.extend_from_slice(&[r, g, b]);
+    }
+
+    // NETSCAPE2.0 application extension - loop forever
+    gif.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+    gif.extend_from_slice(b"NETSCAPE2.0");
+    gif.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+    for frame in frames {
+        // Graphic control extension - holds the delay of the frame
+        gif.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00]);
+        gif.extend_from_slice(&frame.delay.to_le_bytes());
+        gif.extend_from_slice(&[0x00, 0x00]);
+
+        // Image descriptor - covers the whole screen, no local colour table
+        gif.push(0x2C);
+        gif.extend_from_slice(&[0, 0, 0, 0]);
+        gif.extend_from_slice(&width.to_le_bytes());
+        gif.extend_from_slice(&height.to_le_bytes());
+        gif.push(0);
+
+        gif.push(MIN_CODE_SIZE);
+        for block in lzw_compress(&frame.pixels).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.push(0);
+    }
+
+    // Trailer
+    gif.push(0x3B);
+    gif
+}
+
+// Variable length LZW as used by GIF, codes are packed least significant bit first
+fn lzw_compress(pixels: &[u8]) -> Vec<u8> {
+    let clear_code: u16 = 1 << MIN_CODE_SIZE;
+    let end_code = clear_code + 1;
+
+    let mut writer = BitWriter::default();
+    let mut code_size = MIN_CODE_SIZE + 1;
+    let mut next_code = end_code + 1;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+
+    writer.write(clear_code, code_size);
+
+    let mut pixels = pixels.iter();
+    let mut current = match pixels.next() {
+        Some(pixel) => *pixel as u16,
+        None => {
+            writer.write(end_code, code_size);
+            return writer.finish();
+        }
+    };
+
+    for pixel in pixels {
+        if let Some(code) = table.get(&(current, *pixel)) {
+            current = *code;
+            continue;
+        }
+
+        writer.write(current, code_size);
+        if next_code < MAX_CODES {
+            table.insert((current, *pixel), next_code);
+            if next_code == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+            next_code += 1;
+        } else {
+            // The table is full - start over
+            writer.write(clear_code, code_size);
+            table.clear();
+            code_size = MIN_CODE_SIZE + 1;
+            next_code = end_code + 1;
+        }
+        current = *pixel as u16;
+    }
+
+    writer.write(current, code_size);
+    writer.write(end_code, code_size);
+    writer.finish()
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
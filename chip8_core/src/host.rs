@@ -0,0 +1,90 @@
+// What the emulator needs from the machine it runs on
+// Without the standard library there is no operating system to ask for random numbers or the
+// time, so the host supplies them - a hardware RNG and a microsecond timer on a
+// microcontroller, for example
+
+use crate::FRAMES_PER_SECOND;
+
+// Random numbers for CXNN
+pub trait RandomSource {
+    fn random_byte(&mut self) -> u8;
+}
+
+// Any `rand` generator works, which is how `Emulator::new` and `set_seed` get theirs
+impl<T: rand::RngCore> RandomSource for T {
+    fn random_byte(&mut self) -> u8 {
+        rand::Rng::gen(self)
+    }
+}
+
+// A clock that only goes forwards, in microseconds from any starting point
+pub trait Clock {
+    fn now_micros(&self) -> u64;
+}
+
+// Clock backed by `std::time::Instant`, starting at zero when it is created
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now_micros(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+}
+
+// Most frames a pacer hands out at once - after a longer stall the missed time is dropped
+// instead of rushing through it
+const MAX_CATCH_UP_FRAMES: u64 = 4;
+
+// Keeps a main loop at 60 frames a second using the host's clock - call `frames_due` every
+// time round the loop and run that many frames
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FramePacer {
+    // Frames handed out so far, and the time they are counted from
+    frames: u64,
+    start: Option<u64>,
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Frames that have come due since the last call - the first call starts the count
+    pub fn frames_due(&mut self, clock: &impl Clock) -> u32 {
+        let now = clock.now_micros();
+        let start = *self.start.get_or_insert(now);
+        let elapsed = now.saturating_sub(start);
+        // Whole frames in `elapsed`, without the rounding a frame length in microseconds has
+        let total = elapsed * FRAMES_PER_SECOND as u64 / 1_000_000;
+        let due = total.saturating_sub(self.frames);
+        if due > MAX_CATCH_UP_FRAMES {
+            self.frames = total - MAX_CATCH_UP_FRAMES;
+        }
+        let due = due.min(MAX_CATCH_UP_FRAMES);
+        self.frames += due;
+        due as u32
+    }
+
+    // Microseconds until the next frame is due, for hosts that can sleep
+    pub fn micros_until_next(&self, clock: &impl Clock) -> u64 {
+        let Some(start) = self.start else {
+            return 0;
+        };
+        let next = start + ((self.frames + 1) * 1_000_000).div_ceil(FRAMES_PER_SECOND as u64);
+        next.saturating_sub(clock.now_micros())
+    }
+}
@@ -2,18 +2,101 @@
 #![allow(clippy::new_without_default)]
 #![allow(unused_variables)]
 #![allow(clippy::single_match)]
-use rand::Rng;
-use sdl2::{keyboard::Scancode, pixels::Color, render::Canvas, video::Window};
-use std::{fs, thread, time};
+#![allow(clippy::explicit_counter_loop)]
+// Without the `std` feature the emulator runs on bare metal - it never allocates, and random
+// numbers and time come from the host through the traits in `host`
+#![cfg_attr(not(feature = "std"), no_std)]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::ops::Range;
+use rand::{rngs::StdRng, SeedableRng};
+#[cfg(feature = "fs")]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "std")]
+use std::{io, time};
+
+mod alu;
+#[cfg(feature = "std")]
+mod batch;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "alloc")]
+mod blocks;
+mod disasm;
+mod engine;
+#[cfg(feature = "std")]
+pub mod env;
+mod error;
+mod framebuffer;
+#[cfg(feature = "fs")]
+mod gif;
+pub mod host;
+mod machine;
+mod memory_map;
+#[cfg(feature = "alloc")]
+mod png;
+mod quirks;
+#[cfg(feature = "fs")]
+mod recorder;
+pub mod romdb;
+mod sha1;
+mod state;
+mod timing;
+#[cfg(feature = "std")]
+mod trace;
+
+#[cfg(feature = "std")]
+pub use batch::BatchEmulator;
+#[cfg(feature = "std")]
+use bench::Profile;
+#[cfg(feature = "alloc")]
+use blocks::BlockCache;
+#[cfg(feature = "alloc")]
+pub use disasm::disassemble;
+pub use engine::Engine;
+#[cfg(feature = "std")]
+pub use env::Chip8Env;
+pub use error::{EmulatorError, RomError, StateError};
+pub use framebuffer::Framebuffer;
+pub use host::{Clock, FramePacer, RandomSource};
+use machine::{Machine, Screen};
+pub use memory_map::MemoryMap;
+pub use quirks::{Platform, Quirks};
+#[cfg(feature = "fs")]
+use recorder::Recorder;
+#[cfg(feature = "fs")]
+pub use recorder::RecordingOptions;
+pub use romdb::RomInfo;
+#[cfg(feature = "alloc")]
+pub use sha1::sha1_hex;
+pub use sha1::sha1_hex_digits;
+pub use state::STATE_SIZE;
+pub use timing::Timing;
+#[cfg(feature = "std")]
+use trace::RegisterSnapshot;
+#[cfg(feature = "std")]
+pub use trace::{OpcodeClass, TraceEntry, TraceFilter, TraceFormat, TraceOutput, Tracer};
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 
-const RAM_SIZE: usize = 4096;
+pub const RAM_SIZE: usize = 4096;
 const NUM_REGS: usize = 16;
-const STACK_SIZE: usize = 16;
-const NUM_KEYS: usize = 16;
-const START_ADDR: u16 = 0x200;
+// Deepest call stack any quirks profile can ask for
+pub const MAX_STACK_DEPTH: usize = 64;
+pub const NUM_KEYS: usize = 16;
+pub const START_ADDR: u16 = 0x200;
+// The hex font lives in the interpreter area below the program
+const FONT_ADDR: u16 = 0x50;
+pub const FRAMES_PER_SECOND: u32 = 60;
+pub const MAX_ROM_SIZE: usize = RAM_SIZE - START_ADDR as usize;
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 10;
 const FONTS: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -33,7 +116,93 @@ const FONTS: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
-#[derive(Debug)]
+// Colours used to draw the monochrome screen - (red, green, blue)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub background: (u8, u8, u8),
+    pub foreground: (u8, u8, u8),
+}
+
+impl Palette {
+    pub const CLASSIC: Palette = Palette::new((0, 0, 0), (255, 255, 255));
+    pub const AMBER: Palette = Palette::new((40, 20, 0), (255, 176, 0));
+    pub const GREEN: Palette = Palette::new((0, 24, 0), (51, 255, 51));
+    pub const ARCADE: Palette = Palette::new((0, 0, 48), (255, 224, 0));
+
+    pub const fn new(background: (u8, u8, u8), foreground: (u8, u8, u8)) -> Self {
+        Self {
+            background,
+            foreground,
+        }
+    }
+
+    // Accepts a named palette (classic, amber, green, arcade) or two hex colours written as
+    // `background:foreground`, e.g. `000000:33FF33`
+    pub fn parse(text: &str) -> Option<Palette> {
+        match ascii_lowercase(text, &mut [0; 16]) {
+            "classic" => return Some(Palette::CLASSIC),
+            "amber" => return Some(Palette::AMBER),
+            "green" => return Some(Palette::GREEN),
+            "arcade" => return Some(Palette::ARCADE),
+            _ => {}
+        }
+
+        let (background, foreground) = text.split_once(':')?;
+        Some(Palette::new(
+            parse_colour(background)?,
+            parse_colour(foreground)?,
+        ))
+    }
+}
+
+fn parse_colour(hex: &str) -> Option<(u8, u8, u8)> {
+    let hex = hex.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::CLASSIC
+    }
+}
+
+// Area of the screen that changed since it was last drawn, in CHIP-8 pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DirtyRect {
+    pub const FULL_SCREEN: DirtyRect = DirtyRect {
+        x: 0,
+        y: 0,
+        width: SCREEN_WIDTH,
+        height: SCREEN_HEIGHT,
+    };
+
+    // Smallest rectangle covering both rectangles
+    pub fn union(self, other: DirtyRect) -> DirtyRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        DirtyRect {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     ClearScreen,         // 00E0 - Clears screen
     SubroutineReturn,    // 00EE - Returns from a subroutine
@@ -86,94 +255,8 @@ pub enum Instruction {
     PlaceHolder,
 }
 
-pub struct Emulator {
-    // SDL Context - Used for input / output
-    sdl_context: sdl2::Sdl,
-
-    // Program Counter - Keeps track of current place in the game
-    pc: u16,
-
-    // Random Access Memory - Entire game is transferred to RAM - 4KB
-    ram: [u8; RAM_SIZE],
-
-    // Screen is monochrome (1 bit per pixel)
-    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
-
-    // V Registers - referenced from V0 to VF (0 - 15) in Hex
-    v_registers: [u8; NUM_REGS],
-
-    // I register - Used for indexing into RAM for reads and writes
-    i_register: u16,
-
-    // Stack - An awway of 16-bit values the CPU can read and write to - Last in, First Out
-    // Only used when you are entering or exiting a subroutine
-    // An array with a Stack Pointer to know where the top is
-    stack_pointer: u16,
-    stack: [u16; STACK_SIZE],
-
-    // Chip-8 supports 16 different keys, numbered in hex from 0-9, A-F
-    // Arranged in a 4x4 grid
-    keys: [bool; NUM_KEYS],
-
-    // Delay timer - A typical timer, counts down each cycle, performs an action if it hits zero
-    delay_timer: u8,
-
-    // Sound Timer - Counts down every cycle, emits a noise at zero
-    sound_timer: u8,
-
-    // Used for the instruction FX0A - (waiting for key?, register to store key in)
-    waiting_for_key: (bool, u8),
-}
-
-impl Emulator {
-    pub fn new() -> Self {
-        Self {
-            sdl_context: sdl2::init().unwrap(),
-            pc: START_ADDR,
-            ram: [0; RAM_SIZE],
-            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
-            v_registers: [0; NUM_REGS],
-            i_register: 0,
-            stack_pointer: 0,
-            stack: [0; STACK_SIZE],
-            keys: [false; NUM_KEYS],
-            delay_timer: 0,
-            sound_timer: 0,
-            waiting_for_key: (false, 0),
-        }
-    }
-
-    // Loads fonts for hex characters 0-F into memory from index 0x50-0x9F
-    pub fn load_fonts(&mut self) {
-        for (i, byte) in FONTS.iter().enumerate() {
-            self.ram[i + 0x50] = *byte;
-        }
-    }
-
-    pub fn load_rom(&mut self, rom_path: String) {
-        let f: Vec<u8> = fs::read(rom_path).unwrap_or_else(|e| {
-            eprintln!("Failed to load ROM: {}", e);
-            std::process::exit(1);
-        });
-
-        if f.len() <= 4096 {
-            for (i, byte) in f.iter().enumerate() {
-                self.ram[i + 0x200] = *byte;
-            }
-        } else {
-            eprintln!("Maximum ROM size exceeded");
-            std::process::exit(1);
-        }
-    }
-
-    pub fn fetch_instruction(&mut self) -> u16 {
-        let instruction: u16 =
-            ((self.ram[self.pc as usize] as u16) << 8) | (self.ram[self.pc as usize + 1] as u16);
-        self.pc += 2;
-        instruction
-    }
-
-    pub fn decode_instruction(&mut self, instruction: u16) -> Instruction {
+impl Instruction {
+    pub fn decode(instruction: u16) -> Instruction {
         // First hex digit = (instruction >> 12) as u8
         // Last two hex digits = (instruction & 0xFF) as u8
         // Second hex digit = ((instruction >> 8) & 0xF) as u8
@@ -276,353 +359,1396 @@ impl Emulator {
             _ => Instruction::PlaceHolder,
         }
     }
+}
 
-    pub fn execute_instruction(&mut self, instruction: Instruction) {
-        match instruction {
-            Instruction::ClearScreen => {
-                self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
-            }
+// `R` supplies CXNN's random numbers - any `rand` generator, or the host's own `RandomSource`
+pub struct Emulator<R = StdRng> {
+    // Program Counter - Keeps track of current place in the game
+    pc: u16,
 
-            Instruction::CallSubRoutine(nnn) => {
-                self.stack[self.stack_pointer as usize] = self.pc;
-                self.stack_pointer += 1;
-                self.pc = nnn;
-            }
+    // Random Access Memory - Entire game is transferred to RAM - 4KB
+    ram: [u8; RAM_SIZE],
 
-            Instruction::SubroutineReturn => {
-                self.stack_pointer -= 1;
-                self.pc = self.stack[self.stack_pointer as usize];
-            }
+    // Instructions already decoded, by address - an entry is cleared whenever either of its
+    // two bytes is written, so self-modifying code still works
+    decoded: [Option<Instruction>; RAM_SIZE],
+    decode_cache: bool,
 
-            Instruction::Jump(nnn) => {
-                self.pc = nnn;
-            }
+    // Which engine runs frames, and the basic blocks it has decoded so far
+    engine: Engine,
+    #[cfg(feature = "alloc")]
+    blocks: BlockCache<R>,
 
-            Instruction::SkipIfEqual(vx, nn) => {
-                if self.v_registers[vx as usize] == nn {
-                    self.pc += 2;
-                }
-            }
+    // The ROM as loaded from disk and its entry in the ROM database, if it is a known game
+    rom: [u8; MAX_ROM_SIZE],
+    rom_len: usize,
+    rom_info: Option<&'static RomInfo>,
+
+    // Interpreter behaviours the loaded game expects
+    quirks: Quirks,
+
+    // Whether the screen, stack and registers are mirrored into RAM like on the VIP
+    memory_map: MemoryMap,
+
+    // How many instructions run in each 60 Hz frame - sets the speed of the game
+    instructions_per_frame: u32,
+
+    // How the work in a frame is measured, and with VIP timing the machine cycles the frame has
+    // left - negative when the last instruction ran over into the next frame
+    timing: Timing,
+    cycles_left: i32,
+
+    // Set when an instruction waits for the display interrupt, ending the frame early
+    vblank_wait: bool,
+
+    // Screen is monochrome (1 bit per pixel)
+    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+
+    // Number of times the screen changed, for `Framebuffer::change_count`
+    screen_changes: u64,
+
+    // Part of the screen changed since the frontend last drew it - None if nothing changed
+    // Only instructions that touch the screen (00E0, DXYN) mark it
+    dirty_region: Option<DirtyRect>,
+
+    // V Registers - referenced from V0 to VF (0 - 15) in Hex
+    v_registers: [u8; NUM_REGS],
+
+    // I register - Used for indexing into RAM for reads and writes
+    i_register: u16,
+
+    // Stack - An awway of 16-bit values the CPU can read and write to - Last in, First Out
+    // Only used when you are entering or exiting a subroutine
+    // An array with a Stack Pointer to know where the top is
+    stack_pointer: u16,
+    stack: [u16; MAX_STACK_DEPTH],
 
-            Instruction::SkipIfNotEqual(vx, nn) => {
-                if self.v_registers[vx as usize] != nn {
-                    self.pc += 2;
+    // Chip-8 supports 16 different keys, numbered in hex from 0-9, A-F
+    // Arranged in a 4x4 grid
+    keys: [bool; NUM_KEYS],
+
+    // Delay timer - A typical timer, counts down each cycle, performs an action if it hits zero
+    delay_timer: u8,
+
+    // Sound Timer - Counts down every cycle, emits a noise at zero
+    sound_timer: u8,
+
+    // Used for the instruction FX0A - (waiting for key?, register to store key in)
+    waiting_for_key: (bool, u8),
+
+    // Random numbers for CXNN - can be seeded so runs are repeatable
+    rng: R,
+
+    // Number of instructions executed since the emulator was created
+    instruction_count: u64,
+
+    // Colours the screen is drawn with, both in the window and in screenshots
+    palette: Palette,
+
+    // Active gameplay recording, if any
+    #[cfg(feature = "fs")]
+    recorder: Option<Recorder>,
+
+    // Opt-in instruction tracing - off unless a tracer is set
+    #[cfg(feature = "std")]
+    tracer: Option<Tracer>,
+
+    // Time spent decoding and executing, only measured while profiling is on
+    #[cfg(feature = "std")]
+    profile: Option<Profile>,
+}
+
+impl Emulator {
+    pub fn new() -> Self {
+        Self::with_random_source(unseeded_rng())
+    }
+
+    // Makes CXNN produce the same numbers every run
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+}
+
+impl<R: RandomSource> Emulator<R> {
+    // An emulator taking CXNN's random numbers from `random`, e.g. a hardware generator
+    pub fn with_random_source(random: R) -> Self {
+        Self {
+            pc: START_ADDR,
+            ram: [0; RAM_SIZE],
+            decoded: [None; RAM_SIZE],
+            decode_cache: true,
+            engine: Engine::Interpreter,
+            #[cfg(feature = "alloc")]
+            blocks: BlockCache::new(),
+            rom: [0; MAX_ROM_SIZE],
+            rom_len: 0,
+            rom_info: None,
+            quirks: Quirks::default(),
+            memory_map: MemoryMap::Flat,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            timing: Timing::InstructionsPerFrame,
+            cycles_left: 0,
+            vblank_wait: false,
+            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            screen_changes: 0,
+            dirty_region: Some(DirtyRect::FULL_SCREEN),
+            v_registers: [0; NUM_REGS],
+            i_register: 0,
+            stack_pointer: 0,
+            stack: [0; MAX_STACK_DEPTH],
+            keys: [false; NUM_KEYS],
+            delay_timer: 0,
+            sound_timer: 0,
+            waiting_for_key: (false, 0),
+            rng: random,
+            instruction_count: 0,
+            palette: Palette::default(),
+            #[cfg(feature = "fs")]
+            recorder: None,
+            #[cfg(feature = "std")]
+            tracer: None,
+            #[cfg(feature = "std")]
+            profile: None,
+        }
+    }
+
+    // Fetches, decodes and executes a single instruction, tracing it if a tracer is set
+    // On a fault PC is left on the faulting instruction
+    pub fn step(&mut self) -> Result<(), EmulatorError> {
+        let pc = self.pc;
+        machine::check_fetch(pc)?;
+        #[cfg(feature = "std")]
+        let decode_start = self.profile.is_some().then(time::Instant::now);
+        let instruction_code = self.fetch_instruction();
+        let instruction = match self.decoded[pc as usize] {
+            Some(instruction) => instruction,
+            None => {
+                let instruction = Instruction::decode(instruction_code);
+                if self.decode_cache {
+                    self.decoded[pc as usize] = Some(instruction);
                 }
+                instruction
             }
+        };
+        #[cfg(feature = "std")]
+        if let (Some(profile), Some(start)) = (&mut self.profile, decode_start) {
+            profile.decode += start.elapsed();
+        }
+        self.run_instruction(pc, instruction_code, instruction)
+    }
+
+    // Runs an instruction already fetched from `pc` and decoded - shared by `step` and the
+    // block engine so both behave exactly the same
+    fn run_instruction(
+        &mut self,
+        pc: u16,
+        instruction_code: u16,
+        instruction: Instruction,
+    ) -> Result<(), EmulatorError> {
+        self.pc = pc.wrapping_add(2);
+        #[cfg(feature = "std")]
+        let before = match &self.tracer {
+            Some(tracer) if tracer.wants(pc, &instruction) => Some(self.register_snapshot()),
+            _ => None,
+        };
+
+        let cycles = match self.timing {
+            Timing::Vip => timing::vip_cycles(instruction, &self.v_registers),
+            Timing::InstructionsPerFrame => 0,
+        };
+        #[cfg(feature = "std")]
+        let execute_start = self.profile.is_some().then(time::Instant::now);
+        let result = self.execute_instruction(instruction);
+        #[cfg(feature = "std")]
+        if let (Some(profile), Some(start)) = (&mut self.profile, execute_start) {
+            profile.execute += start.elapsed();
+        }
+        if result.is_err() {
+            self.pc = pc;
+        }
 
-            Instruction::SkipIfRegistersEqual(vx, vy) => {
-                if self.v_registers[vx as usize] == self.v_registers[vy as usize] {
-                    self.pc += 2;
+        // Traced before a fault is returned, so a crash dump ends with the faulting instruction
+        #[cfg(feature = "std")]
+        if let Some(before) = before {
+            let entry = TraceEntry {
+                pc,
+                opcode: instruction_code,
+                instruction,
+                changes: before.changes(&self.register_snapshot()),
+            };
+            if let Some(tracer) = &mut self.tracer {
+                if let Err(e) = tracer.record(entry) {
+                    eprintln!("Failed to write trace, tracing stopped: {}", e);
+                    self.tracer = None;
                 }
             }
+        }
+        result?;
 
-            Instruction::SkipIfRegistersNotEqual(vx, vy) => {
-                if self.v_registers[vx as usize] != self.v_registers[vy as usize] {
-                    self.pc += 2;
+        self.instruction_count += 1;
+        match self.timing {
+            Timing::Vip => {
+                self.charge_vip_cycles(instruction, cycles, self.pc == pc.wrapping_add(4))
+            }
+            Timing::InstructionsPerFrame => {
+                if self.quirks.display_wait && matches!(instruction, Instruction::Draw(..)) {
+                    self.vblank_wait = true;
                 }
             }
+        }
+        Ok(())
+    }
 
-            Instruction::Set(vx, vy) => {
-                self.v_registers[vx as usize] = self.v_registers[vy as usize];
+    // Takes an instruction's cost out of the frame's cycles - on the VIP DXYN waits for the
+    // display interrupt before drawing, so the rest of the frame is spent waiting and the
+    // drawing itself is paid for in the next frame
+    fn charge_vip_cycles(&mut self, instruction: Instruction, cycles: i32, skipped: bool) {
+        match instruction {
+            Instruction::Draw(..) => {
+                self.cycles_left = self.cycles_left.min(0) - cycles;
+                self.vblank_wait = true;
             }
-
-            Instruction::Or(vx, vy) => {
-                self.v_registers[vx as usize] |= self.v_registers[vy as usize];
+            Instruction::SkipIfEqual(..)
+            | Instruction::SkipIfNotEqual(..)
+            | Instruction::SkipIfRegistersEqual(..)
+            | Instruction::SkipIfRegistersNotEqual(..)
+            | Instruction::SkipIfKey(_)
+            | Instruction::SkipIfNotKey(_)
+                if skipped =>
+            {
+                self.cycles_left -= cycles + timing::SKIP_TAKEN;
             }
+            _ => self.cycles_left -= cycles,
+        }
+    }
 
-            Instruction::And(vx, vy) => {
-                self.v_registers[vx as usize] &= self.v_registers[vy as usize];
-            }
+    // The decoded instruction cache is on by default - turning it off decodes every
+    // instruction as it is fetched
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache = enabled;
+        self.decoded = [None; RAM_SIZE];
+    }
 
-            Instruction::Xor(vx, vy) => {
-                self.v_registers[vx as usize] ^= self.v_registers[vy as usize];
-            }
+    // The interpreter runs frames by default - the block engine gives the same results faster
+    // Single steps always go through the interpreter
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        #[cfg(feature = "alloc")]
+        self.blocks.clear();
+    }
 
-            Instruction::AddRegisters(vx, vy) => {
-                let (result, overflow) =
-                    self.v_registers[vx as usize].overflowing_add(self.v_registers[vy as usize]);
-                self.v_registers[vx as usize] = result;
-                self.v_registers[0xF] = if overflow { 1 } else { 0 }
-            }
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
 
-            Instruction::Subtract(vx, vy) => {
-                self.v_registers[vx as usize] =
-                    self.v_registers[vx as usize].wrapping_sub(self.v_registers[vy as usize]);
-            }
+    // Replaces the source of CXNN's random numbers
+    pub fn set_random_source(&mut self, random: R) {
+        self.rng = random;
+    }
 
-            Instruction::ReverseSubtract(vx, vy) => {
-                self.v_registers[vx as usize] =
-                    self.v_registers[vy as usize].wrapping_sub(self.v_registers[vx as usize]);
-            }
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
 
-            Instruction::ShiftLeft(vx, vy) => {
-                if (self.v_registers[vx as usize] >> 7) & 1 == 1 {
-                    self.v_registers[0xF] = 1;
-                } else {
-                    self.v_registers[0xF] = 0;
-                }
-                self.v_registers[vx as usize] = self.v_registers[vy as usize] << 1;
-            }
+    // Setters for tools and debuggers poking at a paused machine
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn set_v_register(&mut self, register: usize, value: u8) {
+        self.v_registers[register] = value;
+        self.sync_after_edit(None);
+    }
+
+    pub fn set_i_register(&mut self, value: u16) {
+        self.i_register = value;
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    // Copies `bytes` into RAM from `address` - panics if they run past the end of RAM
+    pub fn write_memory(&mut self, address: usize, bytes: &[u8]) {
+        let written = address..address + bytes.len();
+        self.ram[written.clone()].copy_from_slice(bytes);
+        self.forget_decoded(written.clone());
+        self.sync_after_edit(Some(written));
+    }
+
+    // Keeps the VIP memory map's copies of the screen, stack and registers in step after a
+    // setter changed one side
+    fn sync_after_edit(&mut self, written: Option<Range<usize>>) {
+        if self.memory_map == MemoryMap::Vip {
+            // No instruction ran, so only the bytes written decide which way to copy
+            self.sync_vip_memory(Instruction::PlaceHolder, written);
+        }
+    }
+
+    pub fn v_registers(&self) -> &[u8; NUM_REGS] {
+        &self.v_registers
+    }
+
+    pub fn i_register(&self) -> u16 {
+        self.i_register
+    }
+
+    // Return addresses of the subroutines being run, outermost call first - each points just
+    // after the 2NNN that made the call
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer as usize]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    // The beeper sounds for as long as the sound timer is above zero
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    pub fn memory(&self) -> &[u8; RAM_SIZE] {
+        &self.ram
+    }
+
+    pub fn screen(&self) -> &[bool; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        &self.screen
+    }
+
+    pub fn framebuffer(&self) -> Framebuffer<'_> {
+        Framebuffer::new(&self.screen, SCREEN_WIDTH, self.screen_changes)
+    }
+
+    // True while FX0A is waiting for a key press
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key.0
+    }
+
+    pub fn instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        self.keys[key] = pressed;
+    }
+
+    pub fn set_keys(&mut self, keys: [bool; NUM_KEYS]) {
+        self.keys = keys;
+    }
+
+    // The stack depth is kept between 1 and `MAX_STACK_DEPTH`, and while the VIP memory map is
+    // active to the 24 levels its stack area holds
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = Quirks {
+            stack_depth: quirks.stack_depth.clamp(1, MAX_STACK_DEPTH),
+            ..quirks
+        };
+    }
+
+    // With the stack depth the memory map allows
+    pub fn quirks(&self) -> Quirks {
+        Quirks {
+            stack_depth: self.stack_depth(),
+            ..self.quirks
+        }
+    }
+
+    // Switching to the VIP memory map copies the screen, stack and registers into RAM
+    pub fn set_memory_map(&mut self, memory_map: MemoryMap) {
+        self.memory_map = memory_map;
+        if memory_map == MemoryMap::Vip {
+            self.store_vip_memory();
+        }
+    }
+
+    pub fn memory_map(&self) -> MemoryMap {
+        self.memory_map
+    }
+
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame.max(1);
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    // With `Timing::Vip` the instructions per frame setting is ignored
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        self.cycles_left = 0;
+        self.vblank_wait = false;
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    // Database entry of the loaded ROM - None if the ROM is not a known game
+    pub fn rom_info(&self) -> Option<&'static RomInfo> {
+        self.rom_info
+    }
+
+    // Applies the quirks, speed and palette a known game needs
+    pub fn apply_rom_info(&mut self, info: &RomInfo) {
+        self.set_quirks(info.quirks);
+        self.set_instructions_per_frame(info.instructions_per_frame);
+        self.set_palette(info.palette);
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.mark_dirty(DirtyRect::FULL_SCREEN);
+    }
+
+    pub fn is_screen_dirty(&self) -> bool {
+        self.dirty_region.is_some()
+    }
+
+    // Returns the area of the screen that changed since the last call, clearing it
+    pub fn take_dirty_region(&mut self) -> Option<DirtyRect> {
+        self.dirty_region.take()
+    }
 
-            Instruction::ShiftRight(vx, vy) => {
-                if self.v_registers[vx as usize] & 1 == 1 {
-                    self.v_registers[0xF] = 1;
+    // Converts part of the screen into RGB triplets using the current palette, row by row
+    #[cfg(feature = "alloc")]
+    pub fn region_to_rgb(&self, region: DirtyRect) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(region.width * region.height * 3);
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let (r, g, b) = if self.screen[y * SCREEN_WIDTH + x] {
+                    self.palette.foreground
                 } else {
-                    self.v_registers[0xF] = 0;
-                }
-                self.v_registers[vx as usize] = self.v_registers[vy as usize] >> 1;
+                    self.palette.background
+                };
+                rgb.extend_from_slice(&[r, g, b]);
             }
+        }
+        rgb
+    }
 
-            Instruction::JumpWithOffset(nnn) => {
-                self.pc = nnn + self.v_registers[0] as u16;
-            }
+    // Converts the screen into RGB triplets using the current palette
+    // Every CHIP-8 pixel becomes a `scale` x `scale` square
+    #[cfg(feature = "alloc")]
+    pub fn screen_to_rgb(&self, scale: usize) -> Vec<u8> {
+        screen_to_rgb(&self.screen, self.palette, scale)
+    }
 
-            Instruction::Random(vx, nn) => {
-                let mut rng = rand::thread_rng();
+    // Encodes the screen as a PNG image - does not need a window, so it also works headless
+    #[cfg(feature = "alloc")]
+    pub fn screenshot_png(&self, scale: usize) -> Vec<u8> {
+        let scale = scale.max(1);
+        png::encode_rgb(
+            (SCREEN_WIDTH * scale) as u32,
+            (SCREEN_HEIGHT * scale) as u32,
+            &self.screen_to_rgb(scale),
+        )
+    }
 
-                self.v_registers[vx as usize] = rng.gen::<u8>() & nn;
-            }
+    // Writes a PNG screenshot into `directory` with a timestamped name
+    // Returns the path of the new file
+    #[cfg(feature = "fs")]
+    pub fn save_screenshot(&self, directory: &Path, scale: usize) -> io::Result<PathBuf> {
+        let path = directory.join(timestamped_file_name("chip8", "png"));
+        fs::write(&path, self.screenshot_png(scale))?;
+        Ok(path)
+    }
 
-            Instruction::SetRegister(vx, nn) => {
-                self.v_registers[vx as usize] = nn;
-            }
+    #[cfg(feature = "fs")]
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
 
-            Instruction::SetIndexRegister(nnn) => {
-                self.i_register = nnn;
-            }
+    // Starts capturing frames into a timestamped GIF (and raw / WAV files) in `directory`
+    // Any recording already in progress is thrown away
+    #[cfg(feature = "fs")]
+    pub fn start_recording(
+        &mut self,
+        directory: &Path,
+        options: RecordingOptions,
+    ) -> io::Result<()> {
+        let gif_path = directory.join(timestamped_file_name("chip8", "gif"));
+        self.recorder = Some(Recorder::new(gif_path, self.palette, options)?);
+        Ok(())
+    }
 
-            Instruction::AddToRegister(vx, nn) => {
-                self.v_registers[vx as usize] = self.v_registers[vx as usize].wrapping_add(nn);
-            }
+    // Finishes the recording and returns the paths of the files written
+    #[cfg(feature = "fs")]
+    pub fn stop_recording(&mut self) -> io::Result<Vec<PathBuf>> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(Vec::new()),
+        }
+    }
 
-            Instruction::SkipIfKey(vx) => {
-                if self.keys[self.v_registers[vx as usize] as usize] {
-                    self.pc += 2;
-                }
-            }
+    // Adds the current screen to the recording - `run_frame` calls it at the end of every frame
+    #[cfg(feature = "fs")]
+    fn record_frame(&mut self) -> io::Result<()> {
+        let sound_active = self.sound_timer > 0;
+        match &mut self.recorder {
+            Some(recorder) => recorder.capture(&self.screen, sound_active),
+            None => Ok(()),
+        }
+    }
 
-            Instruction::SkipIfNotKey(vx) => {
-                if !self.keys[self.v_registers[vx as usize] as usize] {
-                    self.pc += 2;
-                }
-            }
+    // Loads fonts for hex characters 0-F into memory from index 0x50-0x9F
+    pub fn load_fonts(&mut self) {
+        for (i, byte) in FONTS.iter().enumerate() {
+            self.ram[i + FONT_ADDR as usize] = *byte;
+        }
+        let font = FONT_ADDR as usize;
+        self.forget_decoded(font..font + FONTS.len());
+    }
 
-            Instruction::GetTimer(vx) => {
-                self.v_registers[vx as usize] = self.delay_timer;
-            }
+    #[cfg(feature = "fs")]
+    pub fn load_rom(&mut self, rom_path: String) -> io::Result<()> {
+        let f: Vec<u8> = fs::read(rom_path)?;
+        Ok(self.load_rom_bytes(&f)?)
+    }
 
-            Instruction::SetSoundTimer(vx) => {
-                self.sound_timer = self.v_registers[vx as usize];
-            }
+    // Copies a ROM into memory at 0x200 and looks it up in the ROM database
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(RomError::TooBig { size: rom.len() });
+        }
 
-            Instruction::SetDelayTimer(vx) => {
-                self.delay_timer = self.v_registers[vx as usize];
-            }
+        for (i, byte) in rom.iter().enumerate() {
+            self.ram[i + 0x200] = *byte;
+        }
+        self.forget_decoded(START_ADDR as usize..START_ADDR as usize + rom.len());
+        self.rom_info = romdb::lookup(rom);
+        self.rom[..rom.len()].copy_from_slice(rom);
+        self.rom_len = rom.len();
+        Ok(())
+    }
 
-            Instruction::AddToIndex(vx) => {
-                self.i_register += self.v_registers[vx as usize] as u16;
-            }
+    // Reads the instruction at PC and moves PC past it - wraps around at the end of RAM
+    pub fn fetch_instruction(&mut self) -> u16 {
+        let instruction = read_opcode(&self.ram, self.pc);
+        self.pc = self.pc.wrapping_add(2);
+        instruction
+    }
 
-            Instruction::GetKey(vx) => {
-                self.waiting_for_key = (true, vx);
-            }
+    pub fn decode_instruction(&mut self, instruction: u16) -> Instruction {
+        Instruction::decode(instruction)
+    }
 
-            Instruction::FontCharacter(vx) => {
-                self.i_register = 0x200 + 5 * (self.v_registers[vx as usize] & 0xF) as u16;
-            }
+    pub fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), EmulatorError> {
+        // RAM the instruction is about to write, so the VIP memory map can pick up the change
+        let i = self.i_register as usize;
+        let written = match instruction {
+            Instruction::StoreMemory(vx) => Some(i..i + vx as usize + 1),
+            Instruction::BinaryDecimalConversion(_) => Some(i..i + 3),
+            _ => None,
+        };
 
-            Instruction::StoreMemory(vx) => {
-                for register in 0..=vx {
-                    self.ram[self.i_register as usize + register as usize] =
-                        self.v_registers[register as usize];
-                }
-            }
+        machine::execute(self, instruction)?;
 
-            Instruction::LoadMemory(vx) => {
-                for register in 0..=vx {
-                    self.v_registers[register as usize] =
-                        self.ram[self.i_register as usize + register as usize];
-                }
+        if self.memory_map == MemoryMap::Vip {
+            self.sync_vip_memory(instruction, written);
+        }
+        Ok(())
+    }
+
+    // Drops the decoded instructions and blocks that overlap the bytes in `written`
+    fn forget_decoded(&mut self, written: Range<usize>) {
+        let start = written.start.saturating_sub(1);
+        let end = written.end.min(RAM_SIZE);
+        if start < end {
+            self.decoded[start..end].fill(None);
+        }
+        #[cfg(feature = "alloc")]
+        self.blocks.forget(written);
+    }
+
+    // Keeps the VIP's copies of the screen, stack and registers in RAM in step with the
+    // emulator's - whichever side the instruction changed is copied to the other
+    fn sync_vip_memory(&mut self, instruction: Instruction, written: Option<Range<usize>>) {
+        let written = written.unwrap_or(0..0);
+
+        if memory_map::overlaps(&written, &memory_map::DISPLAY) {
+            if memory_map::decode_display(&self.ram[memory_map::DISPLAY], &mut self.screen) {
+                self.screen_changes += 1;
+                self.mark_dirty(DirtyRect::FULL_SCREEN);
             }
+        } else if matches!(
+            instruction,
+            Instruction::ClearScreen | Instruction::Draw(..)
+        ) {
+            memory_map::encode_display(&self.screen, &mut self.ram[memory_map::DISPLAY]);
+            self.forget_decoded(memory_map::DISPLAY);
+        }
 
-            Instruction::BinaryDecimalConversion(vx) => {
-                let number = self.v_registers[vx as usize];
-                let hundreds = number / 100;
-                let tens = (number % 100) / 10;
-                let ones = number % 10;
-                self.ram[self.i_register as usize] = hundreds;
-                self.ram[self.i_register as usize + 1] = tens;
-                self.ram[self.i_register as usize + 2] = ones;
+        if memory_map::overlaps(&written, &memory_map::VARIABLES) {
+            self.v_registers
+                .copy_from_slice(&self.ram[memory_map::VARIABLES]);
+        } else if self.ram[memory_map::VARIABLES] != self.v_registers {
+            self.ram[memory_map::VARIABLES].copy_from_slice(&self.v_registers);
+            self.forget_decoded(memory_map::VARIABLES);
+        }
+
+        if memory_map::overlaps(&written, &memory_map::STACK) {
+            for level in 0..self.vip_stack_levels() {
+                let slot = memory_map::stack_slot(level);
+                self.stack[level] = u16::from_be_bytes([self.ram[slot], self.ram[slot + 1]]);
             }
+        }
+    }
+
+    // Levels of the stack mirrored in RAM - calls made before switching to the VIP memory map
+    // can be deeper than its stack area, those stay out of RAM
+    fn vip_stack_levels(&self) -> usize {
+        (self.stack_pointer as usize).min(memory_map::STACK_LEVELS)
+    }
+
+    // Copies the screen, stack and registers into RAM for the VIP memory map
+    fn store_vip_memory(&mut self) {
+        memory_map::encode_display(&self.screen, &mut self.ram[memory_map::DISPLAY]);
+        self.ram[memory_map::VARIABLES].copy_from_slice(&self.v_registers);
+        for level in 0..self.vip_stack_levels() {
+            let slot = memory_map::stack_slot(level);
+            self.ram[slot..slot + 2].copy_from_slice(&self.stack[level].to_be_bytes());
+        }
+        self.forget_decoded(memory_map::STACK.start..RAM_SIZE);
+    }
+
+    // Runs one 60 Hz frame - the frame's share of instructions, then the timers tick once
+    pub fn run_frame(&mut self) -> Result<(), EmulatorError> {
+        self.run_frame_until(|_| false)?;
+        Ok(())
+    }
+
+    // Like `run_frame`, but `should_break` is asked before every instruction and can stop the
+    // frame early, leaving the timers untouched - used by debuggers for breakpoints
+    // Returns true if the frame was stopped early - a fault also stops it, without ticking the
+    // timers
+    pub fn run_frame_until(
+        &mut self,
+        mut should_break: impl FnMut(&Self) -> bool,
+    ) -> Result<bool, EmulatorError> {
+        if self.timing == Timing::Vip {
+            // Cycles left over from a frame stopped early are not saved up
+            self.cycles_left =
+                (self.cycles_left + timing::VIP_CYCLES_PER_FRAME).min(timing::VIP_CYCLES_PER_FRAME);
+        }
 
-            Instruction::Draw(vx, vy, height) => {
-                let mut y = self.v_registers[vy as usize] % (SCREEN_HEIGHT as u8);
-                self.v_registers[0xF] = 0;
-                for sprite_row in 0..height {
-                    if y as usize >= SCREEN_HEIGHT {
+        let mut executed = 0;
+        while self.frame_has_budget(executed) {
+            if self.waiting_for_key.0 {
+                for (key, pressed) in self.keys.iter().enumerate() {
+                    if *pressed {
+                        self.v_registers[self.waiting_for_key.1 as usize] = key as u8;
+                        self.waiting_for_key.0 = false;
                         break;
                     }
-                    let mut x = self.v_registers[vx as usize] % (SCREEN_WIDTH as u8);
-                    let row_data = self.ram[self.i_register as usize + sprite_row as usize];
-
-                    for sprite_column in 0..8 {
-                        if x as usize >= SCREEN_WIDTH {
-                            break;
-                        }
-
-                        let position_on_screen = SCREEN_WIDTH * y as usize + x as usize;
-
-                        let bit = (row_data >> (7 - sprite_column)) & 1;
-
-                        if bit == 1 {
-                            if self.screen[position_on_screen] {
-                                self.screen[position_on_screen] = false;
-                                self.v_registers[0xF] = 1;
-                            } else {
-                                self.screen[position_on_screen] = true;
-                            }
-                        }
-                        x += 1;
+                }
+                if self.waiting_for_key.0 {
+                    break;
+                }
+            }
+            if should_break(self) {
+                return Ok(true);
+            }
+            match self.engine {
+                #[cfg(feature = "alloc")]
+                Engine::Blocks => {
+                    if self.run_block(&mut executed, &mut should_break)? {
+                        return Ok(true);
                     }
-                    y += 1;
+                }
+                _ => {
+                    self.step()?;
+                    executed += 1;
                 }
             }
-            Instruction::PlaceHolder => todo!(),
-        }
-    }
-
-    pub fn new_window(&self) -> Canvas<Window> {
-        let video_subsystem = self.sdl_context.video().unwrap();
-        let window = video_subsystem
-            .window(
-                "Chip-8 Emulator",
-                (SCREEN_WIDTH * 20) as u32,
-                (SCREEN_HEIGHT * 20) as u32,
-            )
-            .position_centered()
-            .build()
-            .unwrap();
-        let mut canvas = window.into_canvas().accelerated().build().unwrap();
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        canvas.present();
-        canvas
-    }
-
-    pub fn update_screen(&mut self, canvas: &mut Canvas<Window>) {
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        for (position, pixel) in self.screen.iter().enumerate() {
-            if *pixel {
-                let x = (position % SCREEN_WIDTH) as i32 * 20;
-                let y = (position / SCREEN_WIDTH) as i32 * 20;
-                canvas
-                    .fill_rect(sdl2::rect::Rect::new(x, y, 20, 20))
-                    .unwrap();
+            if self.vblank_wait {
+                self.vblank_wait = false;
+                break;
             }
         }
-        canvas.present();
+
+        self.tick_timers();
+
+        #[cfg(feature = "fs")]
+        if let Err(e) = self.record_frame() {
+            eprintln!("Failed to record frame: {}", e);
+            self.recorder = None;
+        }
+        Ok(false)
     }
 
-    pub fn game_loop(&mut self, canvas: &mut Canvas<Window>) {
-        let mut last_timer_update = time::Instant::now();
-        let timer_interval = time::Duration::from_millis(16);
+    fn frame_has_budget(&self, executed: u32) -> bool {
+        match self.timing {
+            Timing::InstructionsPerFrame => executed < self.instructions_per_frame,
+            Timing::Vip => self.cycles_left > 0,
+        }
+    }
 
-        loop {
-            let start_time = time::Instant::now();
+    // Runs the block starting at PC, stopping early wherever the frame loop would stop between
+    // two instructions - true if `should_break` asked to stop
+    #[cfg(feature = "alloc")]
+    fn run_block(
+        &mut self,
+        executed: &mut u32,
+        should_break: &mut impl FnMut(&Self) -> bool,
+    ) -> Result<bool, EmulatorError> {
+        machine::check_fetch(self.pc)?;
+        #[cfg(feature = "std")]
+        let decode_start = self.profile.is_some().then(time::Instant::now);
+        let block = self.blocks.get(self.pc, &self.ram);
+        #[cfg(feature = "std")]
+        if let (Some(profile), Some(start)) = (&mut self.profile, decode_start) {
+            profile.decode += start.elapsed();
+        }
+        if block.is_empty() {
+            self.step()?;
+            *executed += 1;
+            return Ok(false);
+        }
 
-            if start_time.duration_since(last_timer_update) >= timer_interval {
-                if self.sound_timer > 0 {
-                    self.sound_timer -= 1;
+        let generation = self.blocks.generation();
+        let compiled =
+            self.timing == Timing::InstructionsPerFrame && self.memory_map == MemoryMap::Flat;
+        #[cfg(feature = "std")]
+        let compiled = compiled && self.tracer.is_none() && self.profile.is_none();
+        let mut first = true;
+        // A block that jumps back to its own start, like a delay loop, runs again straight away
+        loop {
+            for op in block.iter() {
+                if !first {
+                    // Stop if a skip was taken, the block was left or it overwrote itself
+                    if self.pc != op.pc
+                        || !self.frame_has_budget(*executed)
+                        || self.vblank_wait
+                        || self.waiting_for_key.0
+                        || self.blocks.generation() != generation
+                    {
+                        return Ok(false);
+                    }
+                    if should_break(self) {
+                        return Ok(true);
+                    }
                 }
-                if self.delay_timer > 0 {
-                    self.delay_timer -= 1;
+                first = false;
+                match &op.compiled {
+                    Some(run) if compiled => {
+                        self.pc = op.pc.wrapping_add(2);
+                        run(self);
+                        self.instruction_count += 1;
+                    }
+                    _ => self.run_instruction(op.pc, op.opcode, op.instruction)?,
+                }
+                *executed += 1;
+            }
+        }
+    }
+
+    // Counts both timers down once - they run at 60 Hz
+    pub fn tick_timers(&mut self) {
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+    }
+
+    // Restarts the game as if the machine was switched off and on again - memory, registers,
+    // stack, timers and screen are cleared and the fonts and ROM are loaded again
+    // Settings like quirks, speed and palette are kept, as are any tracer or recording
+    pub fn reset(&mut self) {
+        self.pc = START_ADDR;
+        self.ram = [0; RAM_SIZE];
+        self.decoded = [None; RAM_SIZE];
+        #[cfg(feature = "alloc")]
+        self.blocks.clear();
+        machine::clear_screen(self);
+        self.v_registers = [0; NUM_REGS];
+        self.i_register = 0;
+        self.stack_pointer = 0;
+        self.stack = [0; MAX_STACK_DEPTH];
+        self.keys = [false; NUM_KEYS];
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.waiting_for_key = (false, 0);
+        self.instruction_count = 0;
+        self.cycles_left = 0;
+        self.vblank_wait = false;
+
+        self.load_fonts();
+        for (i, byte) in self.rom[..self.rom_len].iter().enumerate() {
+            self.ram[i + START_ADDR as usize] = *byte;
+        }
+        if self.memory_map == MemoryMap::Vip {
+            self.store_vip_memory();
+        }
+    }
+
+    // Jumps back to the start of the program without touching memory, registers, timers or
+    // the screen - the call stack is emptied and a pending key wait is cancelled
+    pub fn soft_reset(&mut self) {
+        self.pc = START_ADDR;
+        self.stack_pointer = 0;
+        self.stack = [0; MAX_STACK_DEPTH];
+        self.waiting_for_key = (false, 0);
+    }
+
+    // Loads the fonts and the game, and applies the game's settings from the ROM database if
+    // it is a known game
+    #[cfg(feature = "fs")]
+    pub fn start_game(&mut self, rom_path: String) -> io::Result<()> {
+        self.load_fonts();
+        self.load_rom(rom_path)?;
+        if let Some(info) = self.rom_info {
+            self.apply_rom_info(info);
+        }
+        Ok(())
+    }
+}
+
+impl<R: RandomSource> Machine for Emulator<R> {
+    fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    // The VIP memory map's stack area is smaller than the flat stack
+    fn stack_depth(&self) -> usize {
+        match self.memory_map {
+            MemoryMap::Flat => self.quirks.stack_depth,
+            MemoryMap::Vip => self.quirks.stack_depth.min(memory_map::STACK_LEVELS),
+        }
+    }
+
+    fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    fn v_registers(&self) -> &[u8; NUM_REGS] {
+        &self.v_registers
+    }
+
+    fn v_registers_mut(&mut self) -> &mut [u8; NUM_REGS] {
+        &mut self.v_registers
+    }
+
+    fn i_register(&self) -> u16 {
+        self.i_register
+    }
+
+    fn set_i_register(&mut self, value: u16) {
+        self.i_register = value;
+    }
+
+    fn stack_pointer(&self) -> usize {
+        self.stack_pointer as usize
+    }
+
+    // With the VIP memory map the return address also goes into the stack area of RAM
+    fn push_return(&mut self, address: u16) {
+        self.stack[self.stack_pointer as usize] = address;
+        if self.memory_map == MemoryMap::Vip {
+            let slot = memory_map::stack_slot(self.stack_pointer as usize);
+            self.ram[slot..slot + 2].copy_from_slice(&address.to_be_bytes());
+            self.forget_decoded(slot..slot + 2);
+        }
+        self.stack_pointer += 1;
+    }
+
+    fn pop_return(&mut self) -> u16 {
+        self.stack_pointer -= 1;
+        self.stack[self.stack_pointer as usize]
+    }
+
+    fn keys(&self) -> &[bool; NUM_KEYS] {
+        &self.keys
+    }
+
+    fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    fn wait_for_key(&mut self, register: u8) {
+        self.waiting_for_key = (true, register);
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        self.rng.random_byte()
+    }
+
+    fn memory(&self) -> &[u8; RAM_SIZE] {
+        &self.ram
+    }
+
+    fn write_ram(&mut self, address: usize, bytes: &[u8]) {
+        let written = address..address + bytes.len();
+        self.ram[written.clone()].copy_from_slice(bytes);
+        self.forget_decoded(written);
+    }
+
+    fn screen_and_ram(&mut self) -> (&mut Screen, &[u8; RAM_SIZE]) {
+        (&mut self.screen, &self.ram)
+    }
+
+    fn count_screen_change(&mut self) {
+        self.screen_changes += 1;
+    }
+
+    fn mark_dirty(&mut self, region: DirtyRect) {
+        self.dirty_region = Some(match self.dirty_region {
+            Some(dirty) => dirty.union(region),
+            None => region,
+        });
+    }
+}
+
+// Tracing and profiling write to files and read the system clock
+#[cfg(feature = "std")]
+impl<R: RandomSource> Emulator<R> {
+    // Starts tracing every instruction the tracer's filter accepts, or stops tracing with None
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    pub fn tracer(&self) -> Option<&Tracer> {
+        self.tracer.as_ref()
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    fn register_snapshot(&self) -> RegisterSnapshot {
+        RegisterSnapshot {
+            v_registers: self.v_registers,
+            i_register: self.i_register,
+            stack_pointer: self.stack_pointer,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    // Makes sure everything traced so far has reached the trace file
+    pub fn flush_trace(&mut self) -> io::Result<()> {
+        match &mut self.tracer {
+            Some(tracer) => tracer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // Starts measuring the time spent decoding and executing instructions, or stops with false
+    // Timing every instruction slows emulation down a lot, so this is for finding out where
+    // the time goes rather than how much there is - compiled blocks are not used meanwhile
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(Profile::default);
+    }
+
+    pub fn profile(&self) -> Option<Profile> {
+        self.profile
+    }
+}
+
+// Lowercases a name into `buffer` without allocating, for looking up names given on the
+// command line - a name too long for the buffer matches nothing
+pub(crate) fn ascii_lowercase<'a>(name: &str, buffer: &'a mut [u8; 16]) -> &'a str {
+    if name.len() > buffer.len() {
+        return "";
+    }
+    let lower = &mut buffer[..name.len()];
+    lower.copy_from_slice(name.as_bytes());
+    lower.make_ascii_lowercase();
+    core::str::from_utf8(lower).unwrap()
+}
+
+// Random numbers for a new emulator, seeded by the operating system
+#[cfg(feature = "entropy")]
+pub(crate) fn unseeded_rng() -> StdRng {
+    StdRng::from_entropy()
+}
+
+// Without the operating system every emulator starts with the same numbers, like seed 0
+#[cfg(not(feature = "entropy"))]
+pub(crate) fn unseeded_rng() -> StdRng {
+    StdRng::seed_from_u64(0)
+}
+
+// The two bytes at `address`, high byte first, wrapping at the end of RAM
+pub(crate) fn read_opcode(ram: &[u8; RAM_SIZE], address: u16) -> u16 {
+    let address = address as usize;
+    (ram[address % RAM_SIZE] as u16) << 8 | ram[(address + 1) % RAM_SIZE] as u16
+}
+
+// XORs an `height`-row sprite read from `address` onto the screen at (start_x, start_y),
+// wrapping or clipping at the edges - returns whether a lit pixel was turned off and whether
+// any pixel changed
+pub(crate) fn draw_sprite(
+    screen: &mut [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    ram: &[u8; RAM_SIZE],
+    address: u16,
+    start_x: usize,
+    start_y: usize,
+    height: u8,
+    wrap: bool,
+) -> (bool, bool) {
+    let mut collision = false;
+    let mut changed = false;
+    for sprite_row in 0..height as usize {
+        let mut y = start_y + sprite_row;
+        if y >= SCREEN_HEIGHT {
+            if !wrap {
+                break;
+            }
+            y %= SCREEN_HEIGHT;
+        }
+        let row_data = ram[(address as usize + sprite_row) % RAM_SIZE];
+
+        for sprite_column in 0..8 {
+            let mut x = start_x + sprite_column;
+            if x >= SCREEN_WIDTH {
+                if !wrap {
+                    break;
                 }
-                last_timer_update = start_time;
+                x %= SCREEN_WIDTH;
             }
+            if (row_data >> (7 - sprite_column)) & 1 == 1 {
+                let pixel = &mut screen[SCREEN_WIDTH * y + x];
+                collision |= *pixel;
+                *pixel = !*pixel;
+                changed = true;
+            }
+        }
+    }
+    (collision, changed)
+}
 
-            let mut event_pump = self.sdl_context.event_pump().unwrap();
-            let pressed_keys = event_pump.keyboard_state();
-            self.keys[0] = pressed_keys.is_scancode_pressed(Scancode::X);
-            self.keys[1] = pressed_keys.is_scancode_pressed(Scancode::Num1);
-            self.keys[2] = pressed_keys.is_scancode_pressed(Scancode::Num2);
-            self.keys[3] = pressed_keys.is_scancode_pressed(Scancode::Num3);
-            self.keys[4] = pressed_keys.is_scancode_pressed(Scancode::Q);
-            self.keys[5] = pressed_keys.is_scancode_pressed(Scancode::W);
-            self.keys[6] = pressed_keys.is_scancode_pressed(Scancode::E);
-            self.keys[7] = pressed_keys.is_scancode_pressed(Scancode::A);
-            self.keys[8] = pressed_keys.is_scancode_pressed(Scancode::S);
-            self.keys[9] = pressed_keys.is_scancode_pressed(Scancode::D);
-            self.keys[0xA] = pressed_keys.is_scancode_pressed(Scancode::Z);
-            self.keys[0xB] = pressed_keys.is_scancode_pressed(Scancode::C);
-            self.keys[0xC] = pressed_keys.is_scancode_pressed(Scancode::Num4);
-            self.keys[0xD] = pressed_keys.is_scancode_pressed(Scancode::R);
-            self.keys[0xE] = pressed_keys.is_scancode_pressed(Scancode::F);
-            self.keys[0xF] = pressed_keys.is_scancode_pressed(Scancode::V);
-
-            for event in event_pump.poll_iter() {
-                match event {
-                    sdl2::event::Event::Quit { .. } => return,
-                    _ => {}
+// Converts a screen into RGB triplets, every pixel becoming a `scale` x `scale` square
+#[cfg(feature = "alloc")]
+pub(crate) fn screen_to_rgb(screen: &[bool], palette: Palette, scale: usize) -> Vec<u8> {
+    let scale = scale.max(1);
+    let mut rgb = Vec::with_capacity(screen.len() * scale * scale * 3);
+    for row in screen.chunks(SCREEN_WIDTH) {
+        for _ in 0..scale {
+            for pixel in row {
+                let (r, g, b) = if *pixel {
+                    palette.foreground
+                } else {
+                    palette.background
+                };
+                for _ in 0..scale {
+                    rgb.extend_from_slice(&[r, g, b]);
                 }
             }
+        }
+    }
+    rgb
+}
 
-            if self.waiting_for_key.0 {
-                for (key, pressed) in self.keys.iter().enumerate() {
-                    if *pressed {
-                        self.v_registers[self.waiting_for_key.1 as usize] = key as u8;
-                        self.waiting_for_key.0 = false;
-                        break;
-                    }
+// Builds a file name like `chip8-20240131-235959-042.png` from the current UTC time
+#[cfg(feature = "fs")]
+fn timestamped_file_name(prefix: &str, extension: &str) -> String {
+    let since_epoch = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let seconds_today = seconds % 86400;
+    format!(
+        "{}-{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}.{}",
+        prefix,
+        year,
+        month,
+        day,
+        seconds_today / 3600,
+        (seconds_today / 60) % 60,
+        seconds_today % 60,
+        since_epoch.subsec_millis(),
+        extension
+    )
+}
+
+// Converts days since 1970-01-01 into a (year, month, day) date
+// Based on Howard Hinnant's `civil_from_days` algorithm
+#[cfg(feature = "fs")]
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emulator_with(rom: &[u8]) -> Emulator {
+        let mut emu = Emulator::new();
+        emu.set_seed(1);
+        emu.load_fonts();
+        emu.load_rom_bytes(rom).unwrap();
+        emu
+    }
+
+    #[test]
+    fn faulting_instruction_is_the_last_traced() {
+        // 6001 then 00EE with nothing on the stack
+        let mut emu = emulator_with(&[0x60, 0x01, 0x00, 0xEE]);
+        emu.set_tracer(Some(Tracer::new(
+            TraceOutput::RingBuffer(8),
+            TraceFormat::Text,
+            TraceFilter::default(),
+        )));
+        emu.step().unwrap();
+        assert_eq!(
+            emu.step(),
+            Err(EmulatorError::StackUnderflow { address: 0x202 })
+        );
+        assert_eq!(emu.pc(), 0x202);
+
+        let tracer = emu.tracer().unwrap();
+        let traced: Vec<(u16, u16)> = tracer.entries().map(|e| (e.pc, e.opcode)).collect();
+        assert_eq!(traced, [(0x200, 0x6001), (0x202, 0x00EE)]);
+    }
+
+    #[test]
+    fn display_wait_draws_one_sprite_per_frame() {
+        // 6000 F029, then DXYN and 1NNN in a loop: 204: D015, 206: 1204
+        let rom = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x15, 0x12, 0x04];
+        for engine in [Engine::Interpreter, Engine::Blocks] {
+            for display_wait in [true, false] {
+                let mut emu = emulator_with(&rom);
+                emu.set_engine(engine);
+                emu.set_quirks(Quirks {
+                    display_wait,
+                    ..Quirks::CHIP8
+                });
+                let mut draws = Vec::new();
+                for _ in 0..60 {
+                    let before = emu.framebuffer().change_count();
+                    emu.run_frame().unwrap();
+                    draws.push(emu.framebuffer().change_count() - before);
                 }
-                if self.waiting_for_key.0 {
-                    continue;
+                if display_wait {
+                    assert_eq!(draws, [1; 60], "{:?}", engine);
+                } else {
+                    assert!(draws.iter().all(|&n| n > 1), "{:?}: {:?}", engine, draws);
                 }
             }
+        }
+    }
+
+    #[test]
+    fn font_character_points_at_the_loaded_glyph() {
+        // 60NN, F029 for every digit, with a high nibble FX29 ignores
+        for digit in 0..16u8 {
+            let mut emu = emulator_with(&[0x60, 0xF0 | digit, 0xF0, 0x29]);
+            emu.step().unwrap();
+            emu.step().unwrap();
+            let glyph = FONT_ADDR + 5 * digit as u16;
+            assert_eq!(emu.i_register(), glyph);
+            let start = 5 * digit as usize;
+            assert_eq!(
+                emu.memory()[glyph as usize..glyph as usize + 5],
+                FONTS[start..start + 5]
+            );
+        }
+    }
+
+    #[test]
+    fn vip_stack_stays_inside_its_area() {
+        // 2200 calls itself until the stack is full
+        for (memory_map, depth) in [(MemoryMap::Flat, MAX_STACK_DEPTH), (MemoryMap::Vip, 24)] {
+            let mut emu = emulator_with(&[0x22, 0x00]);
+            emu.set_memory_map(memory_map);
+            emu.set_quirks(Quirks {
+                stack_depth: MAX_STACK_DEPTH,
+                ..Quirks::default()
+            });
+            assert_eq!(emu.quirks().stack_depth, depth);
+            for _ in 0..depth {
+                emu.step().unwrap();
+            }
+            assert_eq!(
+                emu.step(),
+                Err(EmulatorError::StackOverflow {
+                    address: 0x200,
+                    depth
+                })
+            );
+            assert_eq!(emu.call_stack().len(), depth);
+            if memory_map == MemoryMap::Vip {
+                assert!(emu.memory()[0x202..0xEA0].iter().all(|byte| *byte == 0));
+                assert_eq!(emu.memory()[0xEA0..0xEA2], [0x02, 0x02]);
+            }
+        }
+    }
 
-            let instruction_code = self.fetch_instruction();
-            let instruction = self.decode_instruction(instruction_code);
-            println!("{:X}, {:?}", instruction_code, instruction);
-            self.execute_instruction(instruction);
-
-            self.update_screen(canvas);
-
-            thread::sleep(time::Duration::new(0, 1000))
-        }
-    }
-
-    fn handle_input(&mut self) {
-        let mut event_pump = self.sdl_context.event_pump().unwrap();
-        let pressed_keys = event_pump.keyboard_state();
-        self.keys[0] = pressed_keys.is_scancode_pressed(Scancode::X);
-        self.keys[1] = pressed_keys.is_scancode_pressed(Scancode::Num1);
-        self.keys[2] = pressed_keys.is_scancode_pressed(Scancode::Num2);
-        self.keys[3] = pressed_keys.is_scancode_pressed(Scancode::Num3);
-        self.keys[4] = pressed_keys.is_scancode_pressed(Scancode::Q);
-        self.keys[5] = pressed_keys.is_scancode_pressed(Scancode::W);
-        self.keys[6] = pressed_keys.is_scancode_pressed(Scancode::E);
-        self.keys[7] = pressed_keys.is_scancode_pressed(Scancode::A);
-        self.keys[8] = pressed_keys.is_scancode_pressed(Scancode::S);
-        self.keys[9] = pressed_keys.is_scancode_pressed(Scancode::D);
-        self.keys[0xA] = pressed_keys.is_scancode_pressed(Scancode::Z);
-        self.keys[0xB] = pressed_keys.is_scancode_pressed(Scancode::C);
-        self.keys[0xC] = pressed_keys.is_scancode_pressed(Scancode::Num4);
-        self.keys[0xD] = pressed_keys.is_scancode_pressed(Scancode::R);
-        self.keys[0xE] = pressed_keys.is_scancode_pressed(Scancode::F);
-        self.keys[0xF] = pressed_keys.is_scancode_pressed(Scancode::V);
-
-        for event in event_pump.poll_iter() {
-            match event {
-                sdl2::event::Event::Quit { .. } => return,
-                _ => {}
+    #[test]
+    fn unknown_opcodes_are_faults() {
+        // A 0NNN machine code call, then 8XYF and FXFF which decode to nothing
+        for (rom, opcode) in [
+            ([0x01, 0x23], 0x0123),
+            ([0x81, 0x2F], 0x812F),
+            ([0xF0, 0xFF], 0xF0FF),
+        ] {
+            let mut emu = emulator_with(&rom);
+            assert_eq!(
+                emu.step(),
+                Err(EmulatorError::UnknownOpcode { pc: 0x200, opcode })
+            );
+            assert_eq!(emu.pc(), 0x200);
+            assert_eq!(emu.instruction_count(), 0);
+
+            let mut batch = BatchEmulator::new(&rom, 2).unwrap();
+            batch.run_frame();
+            for lane in 0..2 {
+                assert_eq!(
+                    batch.fault(lane),
+                    Some(&EmulatorError::UnknownOpcode { pc: 0x200, opcode })
+                );
+                assert_eq!(batch.pc(lane), 0x200);
             }
         }
     }
 
-    pub fn start_game(&mut self, rom_path: String) -> Canvas<Window> {
-        self.load_fonts();
-        self.load_rom(rom_path);
-        self.new_window()
+    #[test]
+    fn unknown_opcodes_fault_under_the_block_engine() {
+        let mut emu = emulator_with(&[0x60, 0x05, 0x00, 0x00]);
+        emu.set_engine(Engine::Blocks);
+        assert_eq!(
+            emu.run_frame(),
+            Err(EmulatorError::UnknownOpcode {
+                pc: 0x202,
+                opcode: 0x0000
+            })
+        );
+        assert_eq!(emu.v_registers()[0], 5);
+    }
+
+    #[test]
+    fn memory_past_the_end_of_ram_is_a_fault() {
+        // A jump to FFE, where 00E0 runs and leaves PC past the end of RAM
+        let mut off_the_end = vec![0; MAX_ROM_SIZE];
+        off_the_end[..2].copy_from_slice(&[0x1F, 0xFE]);
+        off_the_end[MAX_ROM_SIZE - 2..].copy_from_slice(&[0x00, 0xE0]);
+        // Each program faults with PC on the instruction at `pc`, reaching for `address`
+        let programs: [(&[u8], u16, u16); 5] = [
+            // AFFE F255 - stores three registers from FFE
+            (&[0xAF, 0xFE, 0xF2, 0x55], 0x202, 0xFFE),
+            // AFFE F265 - loads three registers from FFE
+            (&[0xAF, 0xFE, 0xF2, 0x65], 0x202, 0xFFE),
+            // AFFF F033 - three BCD digits from FFF
+            (&[0xAF, 0xFF, 0xF0, 0x33], 0x202, 0xFFF),
+            // 6010 BFFF - jumps to 100F
+            (&[0x60, 0x10, 0xBF, 0xFF], 0x100F, 0x100F),
+            (&off_the_end, 0x1000, 0x1000),
+        ];
+        for (rom, pc, address) in programs {
+            let fault = EmulatorError::MemoryOutOfRange { pc, address };
+            for engine in [Engine::Interpreter, Engine::Blocks] {
+                let mut emu = emulator_with(rom);
+                emu.set_engine(engine);
+                assert_eq!(emu.run_frame(), Err(fault), "{:?}", engine);
+                assert_eq!(emu.pc(), pc, "{:?}", engine);
+            }
+
+            let mut batch = BatchEmulator::new(rom, 1).unwrap();
+            batch.run_frame();
+            assert_eq!(batch.fault(0), Some(&fault));
+            assert_eq!(batch.pc(0), pc);
+        }
+
+        assert_eq!(
+            EmulatorError::MemoryOutOfRange {
+                pc: 0x202,
+                address: 0xFFE
+            }
+            .to_string(),
+            "access to FFE by the instruction at 202 runs past the end of RAM"
+        );
+
+        // Storing the last two bytes of RAM is fine
+        let mut emu = emulator_with(&[0xAF, 0xFE, 0xF1, 0x55]);
+        emu.step().unwrap();
+        assert_eq!(emu.step(), Ok(()));
+
+        // A PC set by hand with no room for a whole instruction
+        let mut emu = emulator_with(&[]);
+        emu.set_pc(0xFFF);
+        assert_eq!(
+            emu.step(),
+            Err(EmulatorError::MemoryOutOfRange {
+                pc: 0xFFF,
+                address: 0xFFF
+            })
+        );
+        assert_eq!(emu.pc(), 0xFFF);
+    }
+
+    #[test]
+    fn key_skips_use_the_low_nibble_of_vx() {
+        // 6013, E09E skips 6101 while key 3 is held, 1206 loops
+        let rom = [0x60, 0x13, 0xE0, 0x9E, 0x61, 0x01, 0x12, 0x06];
+        for engine in [Engine::Interpreter, Engine::Blocks] {
+            for held in [false, true] {
+                let mut emu = emulator_with(&rom);
+                emu.set_engine(engine);
+                emu.set_key(3, held);
+                emu.run_frame().unwrap();
+                assert_eq!(emu.v_registers()[1], !held as u8, "{:?}", engine);
+            }
+        }
+    }
+
+    #[test]
+    fn index_register_wraps_around() {
+        // 60FF, then F01E and 1202 in a loop, so I goes up by FF every other instruction
+        let rom = [0x60, 0xFF, 0xF0, 0x1E, 0x12, 0x02];
+        for engine in [Engine::Interpreter, Engine::Blocks] {
+            let mut emu = emulator_with(&rom);
+            emu.set_engine(engine);
+            emu.set_instructions_per_frame(100);
+            while emu.instruction_count() < 1000 {
+                emu.run_frame().unwrap();
+            }
+            let adds = emu.instruction_count() / 2;
+            assert_eq!(
+                emu.i_register() as u64,
+                adds * 0xFF % 0x10000,
+                "{:?}",
+                engine
+            );
+        }
     }
 }
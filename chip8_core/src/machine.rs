@@ -0,0 +1,276 @@
+// The instruction set, written once against the `Machine` trait
+// `Emulator` and every instance of a `BatchEmulator` implement it over their own storage, so
+// both run exactly the same instruction code. The hooks with default bodies are for things
+// only the `Emulator` keeps, like the dirty region of the screen
+
+use crate::{
+    alu, draw_sprite, read_opcode, DirtyRect, EmulatorError, Instruction, Quirks, FONT_ADDR,
+    NUM_KEYS, NUM_REGS, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+
+pub(crate) type Screen = [bool; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+// What an instruction can read and change
+pub(crate) trait Machine {
+    fn quirks(&self) -> Quirks;
+    // Deepest call allowed, which can be less than the quirks ask for
+    fn stack_depth(&self) -> usize;
+
+    fn pc(&self) -> u16;
+    fn set_pc(&mut self, pc: u16);
+
+    fn v_registers(&self) -> &[u8; NUM_REGS];
+    fn v_registers_mut(&mut self) -> &mut [u8; NUM_REGS];
+
+    fn i_register(&self) -> u16;
+    fn set_i_register(&mut self, value: u16);
+
+    // Calls currently nested
+    fn stack_pointer(&self) -> usize;
+    // Only called below the stack depth, and above zero for `pop_return`
+    fn push_return(&mut self, address: u16);
+    fn pop_return(&mut self) -> u16;
+
+    fn keys(&self) -> &[bool; NUM_KEYS];
+
+    fn delay_timer(&self) -> u8;
+    fn set_delay_timer(&mut self, value: u8);
+    fn set_sound_timer(&mut self, value: u8);
+
+    // FX0A - stop until a key is pressed, then put it in VX
+    fn wait_for_key(&mut self, register: u8);
+
+    fn random_byte(&mut self) -> u8;
+
+    fn memory(&self) -> &[u8; RAM_SIZE];
+    // Writes from an instruction - anything decoded from those bytes must be decoded again
+    fn write_ram(&mut self, address: usize, bytes: &[u8]);
+
+    // The screen to draw on, and the RAM sprites are read from
+    fn screen_and_ram(&mut self) -> (&mut Screen, &[u8; RAM_SIZE]);
+    // Called once for every instruction that changed a pixel
+    fn count_screen_change(&mut self);
+    // Part of the screen an instruction may have changed
+    fn mark_dirty(&mut self, _region: DirtyRect) {}
+}
+
+// Runs an instruction that has been fetched, with PC already pointing after it
+// On a fault the machine may be partly changed and PC is wherever the instruction left it
+// Inlined so each caller's accessors compile down to plain field accesses
+#[inline]
+pub(crate) fn execute(m: &mut impl Machine, instruction: Instruction) -> Result<(), EmulatorError> {
+    let pc = m.pc();
+
+    match instruction {
+        Instruction::ClearScreen => clear_screen(m),
+
+        Instruction::CallSubRoutine(nnn) => {
+            let depth = m.stack_depth();
+            if m.stack_pointer() >= depth {
+                return Err(EmulatorError::StackOverflow {
+                    address: pc.wrapping_sub(2),
+                    depth,
+                });
+            }
+            m.push_return(pc);
+            m.set_pc(nnn);
+        }
+
+        Instruction::SubroutineReturn => {
+            if m.stack_pointer() == 0 {
+                return Err(EmulatorError::StackUnderflow {
+                    address: pc.wrapping_sub(2),
+                });
+            }
+            let address = m.pop_return();
+            m.set_pc(address);
+        }
+
+        Instruction::Jump(nnn) => m.set_pc(nnn),
+
+        Instruction::SkipIfEqual(vx, nn) => skip_if(m, m.v_registers()[vx as usize] == nn),
+
+        Instruction::SkipIfNotEqual(vx, nn) => skip_if(m, m.v_registers()[vx as usize] != nn),
+
+        Instruction::SkipIfRegistersEqual(vx, vy) => {
+            let v = m.v_registers();
+            skip_if(m, v[vx as usize] == v[vy as usize])
+        }
+
+        Instruction::SkipIfRegistersNotEqual(vx, vy) => {
+            let v = m.v_registers();
+            skip_if(m, v[vx as usize] != v[vy as usize])
+        }
+
+        Instruction::Set(vx, vy)
+        | Instruction::Or(vx, vy)
+        | Instruction::And(vx, vy)
+        | Instruction::Xor(vx, vy)
+        | Instruction::AddRegisters(vx, vy)
+        | Instruction::Subtract(vx, vy)
+        | Instruction::ReverseSubtract(vx, vy)
+        | Instruction::ShiftRight(vx, vy)
+        | Instruction::ShiftLeft(vx, vy) => {
+            let v = m.v_registers();
+            let (result, flag) =
+                alu::execute(instruction, v[vx as usize], v[vy as usize], m.quirks());
+            let registers = m.v_registers_mut();
+            registers[vx as usize] = result;
+            if let Some(flag) = flag {
+                registers[0xF] = flag;
+            }
+        }
+
+        Instruction::JumpWithOffset(nnn) => {
+            let offset_register = if m.quirks().jump_uses_vx {
+                (nnn >> 8) & 0xF
+            } else {
+                0
+            };
+            m.set_pc(nnn + m.v_registers()[offset_register as usize] as u16);
+        }
+
+        Instruction::Random(vx, nn) => {
+            let random = m.random_byte();
+            m.v_registers_mut()[vx as usize] = random & nn;
+        }
+
+        Instruction::SetRegister(vx, nn) => m.v_registers_mut()[vx as usize] = nn,
+
+        Instruction::SetIndexRegister(nnn) => m.set_i_register(nnn),
+
+        Instruction::AddToRegister(vx, nn) => {
+            let register = &mut m.v_registers_mut()[vx as usize];
+            *register = register.wrapping_add(nn);
+        }
+
+        // Only the low nibble of VX reaches the keypad, like on the VIP
+        Instruction::SkipIfKey(vx) => {
+            let pressed = m.keys()[(m.v_registers()[vx as usize] & 0xF) as usize];
+            skip_if(m, pressed);
+        }
+
+        Instruction::SkipIfNotKey(vx) => {
+            let pressed = m.keys()[(m.v_registers()[vx as usize] & 0xF) as usize];
+            skip_if(m, !pressed);
+        }
+
+        Instruction::GetTimer(vx) => m.v_registers_mut()[vx as usize] = m.delay_timer(),
+
+        Instruction::SetSoundTimer(vx) => m.set_sound_timer(m.v_registers()[vx as usize]),
+
+        Instruction::SetDelayTimer(vx) => m.set_delay_timer(m.v_registers()[vx as usize]),
+
+        Instruction::AddToIndex(vx) => m.set_i_register(
+            m.i_register()
+                .wrapping_add(m.v_registers()[vx as usize] as u16),
+        ),
+
+        Instruction::GetKey(vx) => m.wait_for_key(vx),
+
+        Instruction::FontCharacter(vx) => {
+            m.set_i_register(FONT_ADDR + 5 * (m.v_registers()[vx as usize] & 0xF) as u16)
+        }
+
+        Instruction::StoreMemory(vx) => {
+            let (v, count) = (*m.v_registers(), vx as usize + 1);
+            let start = memory_from_i(m, count, pc)?;
+            m.write_ram(start, &v[..count]);
+            if m.quirks().memory_increments_index {
+                m.set_i_register(m.i_register() + count as u16);
+            }
+        }
+
+        Instruction::LoadMemory(vx) => {
+            let count = vx as usize + 1;
+            let start = memory_from_i(m, count, pc)?;
+            let mut loaded = [0; NUM_REGS];
+            loaded[..count].copy_from_slice(&m.memory()[start..start + count]);
+            m.v_registers_mut()[..count].copy_from_slice(&loaded[..count]);
+            if m.quirks().memory_increments_index {
+                m.set_i_register(m.i_register() + count as u16);
+            }
+        }
+
+        Instruction::BinaryDecimalConversion(vx) => {
+            let number = m.v_registers()[vx as usize];
+            let digits = [number / 100, (number % 100) / 10, number % 10];
+            let start = memory_from_i(m, digits.len(), pc)?;
+            m.write_ram(start, &digits);
+        }
+
+        Instruction::Draw(vx, vy, height) => {
+            let v = m.v_registers();
+            let start_x = v[vx as usize] as usize % SCREEN_WIDTH;
+            let start_y = v[vy as usize] as usize % SCREEN_HEIGHT;
+            let wrap = m.quirks().wrap_sprites;
+            if height > 0 {
+                let crosses_edge =
+                    start_x + 8 > SCREEN_WIDTH || start_y + height as usize > SCREEN_HEIGHT;
+                m.mark_dirty(if wrap && crosses_edge {
+                    DirtyRect::FULL_SCREEN
+                } else {
+                    DirtyRect {
+                        x: start_x,
+                        y: start_y,
+                        width: (SCREEN_WIDTH - start_x).min(8),
+                        height: (SCREEN_HEIGHT - start_y).min(height as usize),
+                    }
+                });
+            }
+
+            let i = m.i_register();
+            let (screen, ram) = m.screen_and_ram();
+            let (collision, changed) = draw_sprite(screen, ram, i, start_x, start_y, height, wrap);
+            if changed {
+                m.count_screen_change();
+            }
+            m.v_registers_mut()[0xF] = collision as u8;
+        }
+
+        Instruction::PlaceHolder => {
+            let pc = pc.wrapping_sub(2);
+            return Err(EmulatorError::UnknownOpcode {
+                pc,
+                opcode: read_opcode(m.memory(), pc),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Faults unless a whole instruction can be fetched from `pc`
+pub(crate) fn check_fetch(pc: u16) -> Result<(), EmulatorError> {
+    if pc as usize > RAM_SIZE - 2 {
+        return Err(EmulatorError::MemoryOutOfRange { pc, address: pc });
+    }
+    Ok(())
+}
+
+// Start of the `count` bytes from I, or a fault if they run past the end of RAM
+fn memory_from_i(m: &impl Machine, count: usize, pc: u16) -> Result<usize, EmulatorError> {
+    let start = m.i_register();
+    if start as usize + count > RAM_SIZE {
+        return Err(EmulatorError::MemoryOutOfRange {
+            pc: pc.wrapping_sub(2),
+            address: start,
+        });
+    }
+    Ok(start as usize)
+}
+
+fn skip_if(m: &mut impl Machine, condition: bool) {
+    if condition {
+        m.set_pc(m.pc() + 2);
+    }
+}
+
+// Turns every pixel off, counting it as a change only if one was lit
+pub(crate) fn clear_screen(m: &mut impl Machine) {
+    let (screen, _) = m.screen_and_ram();
+    if screen.contains(&true) {
+        *screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        m.count_screen_change();
+    }
+    m.mark_dirty(DirtyRect::FULL_SCREEN);
+}
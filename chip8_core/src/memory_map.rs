@@ -0,0 +1,68 @@
+// Where the interpreter keeps its own state
+// The flat model keeps the screen, stack and registers outside the 4KB of RAM. The COSMAC VIP
+// kept them in RAM: the interpreter at 0x000-0x1FF, the stack and variables at 0xEA0-0xEFF and
+// the display at 0xF00-0xFFF, and some programs read or write them there
+
+use crate::{ascii_lowercase, SCREEN_HEIGHT, SCREEN_WIDTH};
+use core::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryMap {
+    #[default]
+    Flat,
+    Vip,
+}
+
+impl MemoryMap {
+    // Looks up a memory map by the names used on the command line
+    pub fn from_name(name: &str) -> Option<MemoryMap> {
+        match ascii_lowercase(name, &mut [0; 16]) {
+            "flat" => Some(MemoryMap::Flat),
+            "vip" => Some(MemoryMap::Vip),
+            _ => None,
+        }
+    }
+}
+
+// The stack grows down from the top of its area, two bytes per level, high byte first
+pub(crate) const STACK: Range<usize> = 0xEA0..0xED0;
+// Levels the stack area holds - deeper calls would overwrite the program below it
+pub(crate) const STACK_LEVELS: usize = (STACK.end - STACK.start) / 2;
+
+// V0-VF
+pub(crate) const VARIABLES: Range<usize> = 0xEF0..0xF00;
+
+// One bit per pixel, 8 bytes per row, leftmost pixel in the high bit
+pub(crate) const DISPLAY: Range<usize> = 0xF00..0x1000;
+
+// Address of the return address saved at stack level `level`
+pub(crate) fn stack_slot(level: usize) -> usize {
+    STACK.end - 2 * (level + 1)
+}
+
+pub(crate) fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+pub(crate) fn encode_display(screen: &[bool], display: &mut [u8]) {
+    for (byte, pixels) in display.iter_mut().zip(screen.chunks(8)) {
+        *byte = pixels
+            .iter()
+            .fold(0, |byte, pixel| (byte << 1) | *pixel as u8);
+    }
+}
+
+// Returns true if the screen changed
+pub(crate) fn decode_display(display: &[u8], screen: &mut [bool]) -> bool {
+    let mut changed = false;
+    for (index, pixel) in screen
+        .iter_mut()
+        .enumerate()
+        .take(SCREEN_WIDTH * SCREEN_HEIGHT)
+    {
+        let lit = (display[index / 8] >> (7 - index % 8)) & 1 == 1;
+        changed |= *pixel != lit;
+        *pixel = lit;
+    }
+    changed
+}
@@ -0,0 +1,115 @@
+// Behaviour differences between CHIP-8 interpreters
+// ROMs were written against different interpreters, so each needs the quirks it was tested with
+
+use crate::ascii_lowercase;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    // The original COSMAC VIP interpreter
+    Chip8,
+    // CHIP-48 / SUPER-CHIP on HP48 calculators
+    SuperChip,
+    // Modern XO-CHIP
+    XoChip,
+}
+
+impl Platform {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SCHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+
+    pub const fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::CHIP8,
+            Platform::SuperChip => Quirks::SUPER_CHIP,
+            Platform::XoChip => Quirks::XO_CHIP,
+        }
+    }
+
+    // Looks up a platform by the names used on the command line
+    pub fn from_name(name: &str) -> Option<Platform> {
+        match ascii_lowercase(name, &mut [0; 16]) {
+            "chip8" | "chip-8" | "vip" => Some(Platform::Chip8),
+            "schip" | "superchip" | "super-chip" | "chip48" => Some(Platform::SuperChip),
+            "xochip" | "xo-chip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6 / 8XYE shift VY into VX - otherwise VX is shifted in place
+    pub shift_uses_vy: bool,
+
+    // FX55 / FX65 leave I pointing after the last register stored or loaded
+    pub memory_increments_index: bool,
+
+    // BNNN is treated as BXNN - jumps to XNN plus VX instead of NNN plus V0
+    pub jump_uses_vx: bool,
+
+    // 8XY1 / 8XY2 / 8XY3 reset VF to 0
+    pub logic_resets_vf: bool,
+
+    // DXYN waits for the next 60 Hz vertical blank, so at most one sprite is drawn per frame
+    pub display_wait: bool,
+
+    // Sprites running off the right or bottom edge wrap around to the other side - otherwise
+    // the part past the edge is clipped. The starting position always wraps
+    pub wrap_sprites: bool,
+
+    // Subroutine calls that can be nested before the stack overflows - the VIP had room for 12,
+    // later interpreters 16, homebrew may want more (up to `MAX_STACK_DEPTH`)
+    pub stack_depth: usize,
+}
+
+impl Quirks {
+    pub const CHIP8: Quirks = Quirks {
+        shift_uses_vy: true,
+        memory_increments_index: true,
+        jump_uses_vx: false,
+        logic_resets_vf: true,
+        display_wait: true,
+        wrap_sprites: false,
+        stack_depth: 12,
+    };
+
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        memory_increments_index: false,
+        jump_uses_vx: true,
+        logic_resets_vf: false,
+        display_wait: false,
+        wrap_sprites: false,
+        stack_depth: 16,
+    };
+
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        memory_increments_index: true,
+        jump_uses_vx: false,
+        logic_resets_vf: false,
+        display_wait: false,
+        wrap_sprites: true,
+        stack_depth: 16,
+    };
+}
+
+impl Default for Quirks {
+    // The behaviour this emulator has always had, used for ROMs it does not recognise
+    fn default() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            memory_increments_index: false,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            display_wait: false,
+            wrap_sprites: false,
+            stack_depth: 16,
+        }
+    }
+}
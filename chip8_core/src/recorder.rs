@@ -0,0 +1,216 @@
+// Captures gameplay as an animated GIF, with an optional raw video dump and WAV audio track
+// The raw dump is RGB24 at 60 frames per second, so it can be muxed with the audio later, e.g.
+// ffmpeg -f rawvideo -pix_fmt rgb24 -s 64x32 -r 60 -i clip.raw -i clip.wav clip.mp4
+
+use crate::{gif, screen_to_rgb, Palette, FRAMES_PER_SECOND, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+const SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / FRAMES_PER_SECOND;
+const TONE_HZ: u32 = 440;
+const AMPLITUDE: i16 = 8000;
+// Most samples a WAV file can hold - its sizes are 32-bit, about 13 hours at this sample rate
+const MAX_WAV_SAMPLES: u64 = (u32::MAX as u64 - 36) / 2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecordingOptions {
+    // Also write every frame as raw RGB24 data
+    pub raw_frames: bool,
+
+    // Also write the sound timer's beeper as a WAV file
+    pub audio: bool,
+}
+
+pub struct Recorder {
+    gif_path: PathBuf,
+    palette: Palette,
+
+    // Distinct consecutive frames - (pixels as palette indices, number of 60 Hz frames shown)
+    frames: Vec<(Vec<u8>, u32)>,
+
+    raw: Option<(PathBuf, BufWriter<File>)>,
+    audio: Option<AudioTrack>,
+}
+
+struct AudioTrack {
+    path: PathBuf,
+    file: BufWriter<File>,
+    samples_written: u64,
+}
+
+impl Recorder {
+    // Creates the output files - `gif_path` decides the names of the raw and WAV files too
+    pub fn new(gif_path: PathBuf, palette: Palette, options: RecordingOptions) -> io::Result<Self> {
+        let raw = if options.raw_frames {
+            let path = gif_path.with_extension("raw");
+            let file = BufWriter::new(File::create(&path)?);
+            Some((path, file))
+        } else {
+            None
+        };
+
+        let audio = if options.audio {
+            let path = gif_path.with_extension("wav");
+            let mut file = BufWriter::new(File::create(&path)?);
+            // The sizes in the header are filled in once the recording is finished
+            write_wav_header(&mut file, 0)?;
+            Some(AudioTrack {
+                path,
+                file,
+                samples_written: 0,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            gif_path,
+            palette,
+            frames: Vec::new(),
+            raw,
+            audio,
+        })
+    }
+
+    // Adds one 60 Hz frame to the recording
+    pub fn capture(&mut self, screen: &[bool], sound_active: bool) -> io::Result<()> {
+        let pixels: Vec<u8> = screen.iter().map(|pixel| *pixel as u8).collect();
+        match self.frames.last_mut() {
+            Some((last, count)) if *last == pixels => *count += 1,
+            _ => self.frames.push((pixels, 1)),
+        }
+
+        if let Some((_, file)) = &mut self.raw {
+            file.write_all(&screen_to_rgb(screen, self.palette, 1))?;
+        }
+
+        if let Some(track) = &mut self.audio {
+            // Past the WAV size limit the audio stops, the GIF and raw frames carry on
+            let samples = (MAX_WAV_SAMPLES - track.samples_written).min(SAMPLES_PER_FRAME as u64);
+            for _ in 0..samples {
+                let sample = if sound_active {
+                    square_wave(track.samples_written)
+                } else {
+                    0
+                };
+                track.file.write_all(&sample.to_le_bytes())?;
+                track.samples_written += 1;
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the GIF and completes the other files, returning the paths of everything written
+    pub fn finish(self) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+
+        // GIF delays are in hundredths of a second, which 60 Hz frames do not divide evenly
+        // Rounding the running total instead of each delay keeps the clip from drifting
+        let mut elapsed_frames = 0;
+        let frames: Vec<gif::GifFrame> = self
+            .frames
+            .into_iter()
+            .map(|(pixels, count)| {
+                let start = (elapsed_frames * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+                elapsed_frames += count;
+                let end = (elapsed_frames * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+                gif::GifFrame {
+                    pixels,
+                    delay: (end - start).min(u16::MAX as u32) as u16,
+                }
+            })
+            .collect();
+        let colours = [self.palette.background, self.palette.foreground];
+        fs::write(
+            &self.gif_path,
+            gif::encode_animation(SCREEN_WIDTH as u16, SCREEN_HEIGHT as u16, colours, &frames),
+        )?;
+        paths.push(self.gif_path);
+
+        if let Some((path, mut file)) = self.raw {
+            file.flush()?;
+            paths.push(path);
+        }
+
+        if let Some(mut track) = self.audio {
+            track.file.seek(SeekFrom::Start(0))?;
+            write_wav_header(&mut track.file, track.samples_written)?;
+            track.file.flush()?;
+            paths.push(track.path);
+        }
+
+        Ok(paths)
+    }
+}
+
+// The beeper's tone at sample `index` of the track
+fn square_wave(index: u64) -> i16 {
+    if (index * TONE_HZ as u64 * 2 / SAMPLE_RATE as u64).is_multiple_of(2) {
+        AMPLITUDE
+    } else {
+        -AMPLITUDE
+    }
+}
+
+// 16-bit mono PCM
+fn write_wav_header(file: &mut impl Write, samples: u64) -> io::Result<()> {
+    if samples > MAX_WAV_SAMPLES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "audio is too long for a WAV file",
+        ));
+    }
+    let data_size = samples as u32 * 2;
+    file.write_all(b"RIFF")?;
+    file.write_all(&(36 + data_size).to_le_bytes())?;
+    file.write_all(b"WAVE")?;
+    file.write_all(b"fmt ")?;
+    file.write_all(&16u32.to_le_bytes())?;
+    file.write_all(&1u16.to_le_bytes())?; // PCM
+    file.write_all(&1u16.to_le_bytes())?; // Mono
+    file.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    file.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?; // Bytes per second
+    file.write_all(&2u16.to_le_bytes())?; // Bytes per sample
+    file.write_all(&16u16.to_le_bytes())?; // Bits per sample
+    file.write_all(b"data")?;
+    file.write_all(&data_size.to_le_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_wave_keeps_its_pitch_past_32_bit_sample_counts() {
+        // The old u32 arithmetic overflowed after about 110 seconds - a 440 Hz square wave
+        // changes sign 880 times a second however long the recording has run
+        for start in [0, 200 * SAMPLE_RATE as u64, u32::MAX as u64] {
+            let flips = (start..start + SAMPLE_RATE as u64)
+                .filter(|&index| square_wave(index) != square_wave(index + 1))
+                .count();
+            assert_eq!(flips, 2 * TONE_HZ as usize, "starting at sample {}", start);
+        }
+    }
+
+    #[test]
+    fn wav_header_holds_sizes_up_to_the_limit() {
+        let mut header = Vec::new();
+        write_wav_header(&mut header, MAX_WAV_SAMPLES).unwrap();
+        let riff_size = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let data_size = u32::from_le_bytes(header[40..44].try_into().unwrap());
+        assert_eq!(riff_size as u64, 36 + MAX_WAV_SAMPLES * 2);
+        assert_eq!(data_size as u64, MAX_WAV_SAMPLES * 2);
+    }
+
+    #[test]
+    fn wav_header_rejects_audio_past_4_gib() {
+        let mut header = Vec::new();
+        assert!(write_wav_header(&mut header, MAX_WAV_SAMPLES + 1).is_err());
+        assert!(header.is_empty());
+    }
+}
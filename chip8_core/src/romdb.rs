@@ -0,0 +1,272 @@
+// Built-in database of known ROMs, keyed by the SHA-1 of the ROM file
+// Lets the emulator pick the quirks, speed and colours a game needs without any configuration
+
+use crate::{sha1::sha1_hex_digits, Palette, Platform, Quirks};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomInfo {
+    pub sha1: &'static str,
+    pub title: &'static str,
+    pub author: &'static str,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub instructions_per_frame: u32,
+    // Which CHIP-8 keys the game uses
+    pub key_hints: &'static str,
+    pub palette: Palette,
+}
+
+// Games that other code knows more about, like where `env` finds their score
+pub(crate) const PONG_SHA1: &str = "b232ef880bd6060fb45fa6effed7edf0ae95670e";
+pub(crate) const PONG_2_SHA1: &str = "a60611339661e3ab2d8af024ad1da5880a6f8665";
+
+pub const ROMS: &[RomInfo] = &[
+    RomInfo {
+        sha1: "d40abc54374e4343639f993e897e00904ddf85d9",
+        title: "Blinky",
+        author: "Hans Christian Egeberg",
+        platform: Platform::SuperChip,
+        quirks: Quirks::SUPER_CHIP,
+        instructions_per_frame: 15,
+        key_hints: "3 up, 6 down, 7 left, 8 right",
+        palette: Palette::ARCADE,
+    },
+    RomInfo {
+        sha1: "6f6509f38220e057a7e32ebb22dd353c1078e3e7",
+        title: "Blitz",
+        author: "David Winter",
+        platform: Platform::Chip8,
+        quirks: Quirks {
+            wrap_sprites: true,
+            ..Quirks::CHIP8
+        },
+        instructions_per_frame: 10,
+        key_hints: "5 drop bomb",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "f13766c14aeb02ad8d4d103cb5eadd282d20cddc",
+        title: "Brix",
+        author: "Andreas Gustafsson",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "4 left, 6 right",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "2d10c07b532f4fa7c07a07324ba26ca39fe484fd",
+        title: "Connect 4",
+        author: "David Winter",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "4 left, 6 right, 5 drop",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "5260f8931e0e9f41e555b382a14a88368e3ed886",
+        title: "Guess",
+        author: "David Winter",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "5 yes, any other key no",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "050f07a54371da79f924dd0227b89d07b4f2aed0",
+        title: "Hidden!",
+        author: "David Winter",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "2 up, 8 down, 4 left, 6 right, 5 turn card",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "1ba58656810b67fd131eb9af3e3987863bf26c90",
+        title: "IBM Logo",
+        author: "Unknown",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "None",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "f100197f0f2f05b4f3c8c31ab9c2c3930d3e9571",
+        title: "Space Invaders",
+        author: "David Winter",
+        platform: Platform::SuperChip,
+        quirks: Quirks::SUPER_CHIP,
+        instructions_per_frame: 15,
+        key_hints: "4 left, 6 right, 5 fire / start",
+        palette: Palette::GREEN,
+    },
+    RomInfo {
+        sha1: "d6fa9dc9005dc0496f39ba52fef56f9fd0a5a158",
+        title: "Kaleidoscope",
+        author: "Joseph Weisbecker",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "2 up, 8 down, 4 left, 6 right, 0 repeat pattern",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "b9272ae1acdaaa79ab649f6b48b72088ca2b1d74",
+        title: "Maze",
+        author: "David Winter",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "None",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "d979858bb9ffd07b48f52f92a8bcac0199f3623e",
+        title: "Merlin",
+        author: "David Winter",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "4 5 1 2 pick a square",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "0d0cc129dad3c45ba672f85fec71a668232212cc",
+        title: "Missile Command",
+        author: "David Winter",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "8 fire",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: PONG_SHA1,
+        title: "Pong",
+        author: "Paul Vervalin",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "1 / 4 left paddle, C / D right paddle",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: PONG_2_SHA1,
+        title: "Pong 2",
+        author: "David Winter",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "1 / 4 left paddle, C / D right paddle",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "1293db0ccccbe7dd3fc5a09a2abc5d7b175e18e0",
+        title: "Puzzle",
+        author: "Unknown",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "2 up, 8 down, 4 left, 6 right",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "1bdb4ddaa7049266fa3226851f28855a365cfd12",
+        title: "Syzygy",
+        author: "Roy Trevino",
+        platform: Platform::SuperChip,
+        quirks: Quirks::SUPER_CHIP,
+        instructions_per_frame: 15,
+        key_hints: "3 up, 6 down, 7 left, 8 right, E / F border on or off",
+        palette: Palette::GREEN,
+    },
+    RomInfo {
+        sha1: "18b9d15f4c159e1f0ed58c2d8ec1d89325d3a3b6",
+        title: "Tank",
+        author: "Unknown",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "2 down, 8 up, 4 left, 6 right, 5 fire",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "5f518084744bf3cb8733f6e5454dfd1634320563",
+        title: "Tetris",
+        author: "Fran Dachille",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 15,
+        key_hints: "4 rotate, 5 left, 6 right, 1 drop",
+        palette: Palette::AMBER,
+    },
+    RomInfo {
+        sha1: "429d455a4bc53167942bf6fd934d72b0f648dce3",
+        title: "Tic-Tac-Toe",
+        author: "David Winter",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "1 - 9 pick a square",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "bdb92475acfe11bc7814a2f5eade13fcd09b756a",
+        title: "UFO",
+        author: "Lutz V",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "4 fire left, 5 fire up, 6 fire right",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "da710f631f8e35534d0b9170bcf892a60f49c43d",
+        title: "Vertical Brix",
+        author: "Paul Robson",
+        platform: Platform::Chip8,
+        quirks: Quirks {
+            wrap_sprites: true,
+            ..Quirks::CHIP8
+        },
+        instructions_per_frame: 10,
+        key_hints: "1 up, 4 down, 7 start",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "ade839585ddeb0e3633177df03c1d91589e629eb",
+        title: "Vers",
+        author: "JMN",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "7 / A / 1 / 2 left player, B / F / C / D right player",
+        palette: Palette::CLASSIC,
+    },
+    RomInfo {
+        sha1: "d666688a8fce468a7d88b536bc1ef5f35ba12031",
+        title: "Wipe Off",
+        author: "Joseph Weisbecker",
+        platform: Platform::Chip8,
+        quirks: Quirks::CHIP8,
+        instructions_per_frame: 10,
+        key_hints: "4 left, 6 right",
+        palette: Palette::CLASSIC,
+    },
+];
+
+// Looks up a ROM by the SHA-1 of its contents
+pub fn lookup(rom: &[u8]) -> Option<&'static RomInfo> {
+    let hex = sha1_hex_digits(rom);
+    lookup_hash(core::str::from_utf8(&hex).unwrap())
+}
+
+// Looks up a ROM by a hex encoded SHA-1
+pub fn lookup_hash(sha1: &str) -> Option<&'static RomInfo> {
+    ROMS.iter()
+        .find(|info| info.sha1.eq_ignore_ascii_case(sha1))
+}
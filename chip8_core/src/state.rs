@@ -0,0 +1,237 @@
+// Saving and restoring a running machine
+// A state holds everything a game can change - registers, stack, timers, keys, RAM and the
+// screen - in a fixed-size, versioned layout, with numbers stored big-endian. Settings like
+// quirks and speed are not part of it, and neither is the random number generator, so CXNN
+// carries on from wherever it was
+
+use crate::{
+    machine::Machine, DirtyRect, Emulator, MemoryMap, RandomSource, StateError, MAX_STACK_DEPTH,
+    NUM_KEYS, NUM_REGS, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+
+const MAGIC: &[u8; 4] = b"CH8S";
+const VERSION: u8 = 1;
+
+pub const STATE_SIZE: usize = MAGIC.len()
+    + 1 // version
+    + 2 // PC
+    + 2 // I
+    + 1 // stack pointer
+    + 2 * MAX_STACK_DEPTH
+    + NUM_REGS
+    + 1 // delay timer
+    + 1 // sound timer
+    + 2 // key wait and its register
+    + NUM_KEYS
+    + 4 // VIP cycles left
+    + 1 // waiting for the vertical blank
+    + 8 // instruction count
+    + SCREEN_WIDTH * SCREEN_HEIGHT / 8
+    + RAM_SIZE;
+
+struct Writer<'a> {
+    out: &'a mut [u8],
+    at: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.out[self.at..self.at + bytes.len()].copy_from_slice(bytes);
+        self.at += bytes.len();
+    }
+
+    fn flag(&mut self, flag: bool) {
+        self.bytes(&[flag as u8]);
+    }
+}
+
+struct Reader<'a> {
+    state: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.state[self.at..self.at + len];
+        self.at += len;
+        bytes
+    }
+
+    fn array<const N: usize>(&mut self) -> [u8; N] {
+        self.bytes(N).try_into().unwrap()
+    }
+
+    fn byte(&mut self) -> u8 {
+        self.bytes(1)[0]
+    }
+
+    fn flag(&mut self) -> bool {
+        self.byte() != 0
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_be_bytes(self.array())
+    }
+}
+
+impl<R: RandomSource> Emulator<R> {
+    // Writes the machine's state into `state`, see `load_state`
+    pub fn save_state(&self, state: &mut [u8; STATE_SIZE]) {
+        let mut out = Writer { out: state, at: 0 };
+        out.bytes(MAGIC);
+        out.bytes(&[VERSION]);
+        out.bytes(&self.pc.to_be_bytes());
+        out.bytes(&self.i_register.to_be_bytes());
+        out.bytes(&[self.stack_pointer as u8]);
+        for address in self.stack {
+            out.bytes(&address.to_be_bytes());
+        }
+        out.bytes(&self.v_registers);
+        out.bytes(&[self.delay_timer, self.sound_timer]);
+        out.flag(self.waiting_for_key.0);
+        out.bytes(&[self.waiting_for_key.1]);
+        for pressed in self.keys {
+            out.flag(pressed);
+        }
+        out.bytes(&self.cycles_left.to_be_bytes());
+        out.flag(self.vblank_wait);
+        out.bytes(&self.instruction_count.to_be_bytes());
+        for pixels in self.screen.chunks_exact(8) {
+            let byte = pixels
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (bit, lit)| byte | (*lit as u8) << (7 - bit));
+            out.bytes(&[byte]);
+        }
+        out.bytes(&self.ram);
+    }
+
+    // Puts the machine back the way it was when `state` was saved - nothing changes if the
+    // state is not valid
+    pub fn load_state(&mut self, state: &[u8; STATE_SIZE]) -> Result<(), StateError> {
+        let mut input = Reader { state, at: 0 };
+        if input.bytes(MAGIC.len()) != MAGIC {
+            return Err(StateError::NotAState);
+        }
+        let version = input.byte();
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        let pc = input.u16();
+        let i_register = input.u16();
+        let stack_pointer = input.byte() as u16;
+        let mut stack = [0; MAX_STACK_DEPTH];
+        for address in &mut stack {
+            *address = input.u16();
+        }
+        let v_registers = input.array();
+        let delay_timer = input.byte();
+        let sound_timer = input.byte();
+        let waiting_for_key = (input.flag(), input.byte());
+        let mut keys = [false; NUM_KEYS];
+        for pressed in &mut keys {
+            *pressed = input.flag();
+        }
+        let cycles_left = i32::from_be_bytes(input.array());
+        let vblank_wait = input.flag();
+        let instruction_count = u64::from_be_bytes(input.array());
+        let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        for (pixels, byte) in screen
+            .chunks_exact_mut(8)
+            .zip(input.bytes(SCREEN_WIDTH * SCREEN_HEIGHT / 8))
+        {
+            for (bit, pixel) in pixels.iter_mut().enumerate() {
+                *pixel = (byte >> (7 - bit)) & 1 == 1;
+            }
+        }
+        let ram = input.array::<RAM_SIZE>();
+
+        if pc as usize > RAM_SIZE - 2 {
+            return Err(StateError::Invalid(
+                "PC leaves no room for an instruction in RAM",
+            ));
+        }
+        // Checked against the depth this machine allows, which the VIP memory map can lower
+        if stack_pointer as usize > self.stack_depth() {
+            return Err(StateError::Invalid(
+                "stack pointer is past the end of the stack",
+            ));
+        }
+        if waiting_for_key.1 as usize >= NUM_REGS {
+            return Err(StateError::Invalid("key wait register is not V0 to VF"));
+        }
+
+        self.pc = pc;
+        self.i_register = i_register;
+        self.stack_pointer = stack_pointer;
+        self.stack = stack;
+        self.v_registers = v_registers;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.waiting_for_key = waiting_for_key;
+        self.keys = keys;
+        self.cycles_left = cycles_left;
+        self.vblank_wait = vblank_wait;
+        self.instruction_count = instruction_count;
+        // The change count only goes up, so frontends know to redraw
+        if self.screen != screen {
+            self.screen = screen;
+            self.screen_changes += 1;
+        }
+        self.ram = ram;
+        self.decoded = [None; RAM_SIZE];
+        #[cfg(feature = "alloc")]
+        self.blocks.clear();
+        self.mark_dirty(DirtyRect::FULL_SCREEN);
+        if self.memory_map == MemoryMap::Vip {
+            self.store_vip_memory();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAGIC, STATE_SIZE};
+    use crate::{Emulator, MemoryMap, Quirks, StateError, MAX_STACK_DEPTH};
+
+    const PC_AT: usize = MAGIC.len() + 1;
+    const STACK_POINTER_AT: usize = PC_AT + 4;
+
+    #[test]
+    fn states_the_machine_cannot_run_are_rejected() {
+        // The VIP memory map has room for fewer calls than the quirks ask for
+        let mut emu = Emulator::new();
+        emu.set_quirks(Quirks {
+            stack_depth: MAX_STACK_DEPTH,
+            ..Quirks::default()
+        });
+        emu.set_memory_map(MemoryMap::Vip);
+        let depth = emu.quirks().stack_depth;
+        assert!(depth < MAX_STACK_DEPTH);
+        let mut state = Box::new([0; STATE_SIZE]);
+        emu.save_state(&mut state);
+
+        // A PC with room for one more instruction, and a full stack, are fine
+        let mut valid = state.clone();
+        valid[PC_AT..PC_AT + 2].copy_from_slice(&0xFFEu16.to_be_bytes());
+        valid[STACK_POINTER_AT] = depth as u8;
+        assert_eq!(emu.load_state(&valid), Ok(()));
+        assert_eq!(emu.pc(), 0xFFE);
+
+        let mut pc_at_the_end = state.clone();
+        pc_at_the_end[PC_AT..PC_AT + 2].copy_from_slice(&0xFFFu16.to_be_bytes());
+        assert!(matches!(
+            emu.load_state(&pc_at_the_end),
+            Err(StateError::Invalid(_))
+        ));
+
+        let mut stack_too_deep = state.clone();
+        stack_too_deep[STACK_POINTER_AT] = depth as u8 + 1;
+        assert!(matches!(
+            emu.load_state(&stack_too_deep),
+            Err(StateError::Invalid(_))
+        ));
+        assert_eq!(emu.pc(), 0xFFE);
+    }
+}
@@ -0,0 +1,268 @@
+// Opt-in instruction tracing
+// Each traced step records the address, raw opcode, decoded instruction and which registers changed
+// Traces are either written out as they happen (text or JSON lines) or kept in a bounded ring
+// buffer, so the last N instructions can be dumped when something goes wrong
+
+use crate::Instruction;
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    ops::RangeInclusive,
+};
+#[cfg(feature = "fs")]
+use std::{fs::File, io::BufWriter, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    // One human readable line per instruction
+    Text,
+    // One JSON object per line
+    JsonLines,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpcodeClass {
+    Flow,       // 00EE, 1NNN, 2NNN, BNNN
+    Skip,       // 3XNN, 4XNN, 5XY0, 9XY0
+    Arithmetic, // 6XNN, 7XNN, 8XYN, CXNN
+    Memory,     // ANNN, FX1E, FX29, FX33, FX55, FX65
+    Display,    // 00E0, DXYN
+    Input,      // EX9E, EXA1, FX0A
+    Timer,      // FX07, FX15, FX18
+    Unknown,
+}
+
+impl Instruction {
+    pub fn class(&self) -> OpcodeClass {
+        match self {
+            Instruction::SubroutineReturn
+            | Instruction::Jump(_)
+            | Instruction::CallSubRoutine(_)
+            | Instruction::JumpWithOffset(_) => OpcodeClass::Flow,
+
+            Instruction::SkipIfEqual(..)
+            | Instruction::SkipIfNotEqual(..)
+            | Instruction::SkipIfRegistersEqual(..)
+            | Instruction::SkipIfRegistersNotEqual(..) => OpcodeClass::Skip,
+
+            Instruction::SetRegister(..)
+            | Instruction::AddToRegister(..)
+            | Instruction::Set(..)
+            | Instruction::Or(..)
+            | Instruction::And(..)
+            | Instruction::Xor(..)
+            | Instruction::AddRegisters(..)
+            | Instruction::Subtract(..)
+            | Instruction::ReverseSubtract(..)
+            | Instruction::ShiftRight(..)
+            | Instruction::ShiftLeft(..)
+            | Instruction::Random(..) => OpcodeClass::Arithmetic,
+
+            Instruction::SetIndexRegister(_)
+            | Instruction::AddToIndex(_)
+            | Instruction::FontCharacter(_)
+            | Instruction::BinaryDecimalConversion(_)
+            | Instruction::StoreMemory(_)
+            | Instruction::LoadMemory(_) => OpcodeClass::Memory,
+
+            Instruction::ClearScreen | Instruction::Draw(..) => OpcodeClass::Display,
+
+            Instruction::SkipIfKey(_) | Instruction::SkipIfNotKey(_) | Instruction::GetKey(_) => {
+                OpcodeClass::Input
+            }
+
+            Instruction::GetTimer(_)
+            | Instruction::SetDelayTimer(_)
+            | Instruction::SetSoundTimer(_) => OpcodeClass::Timer,
+
+            Instruction::PlaceHolder => OpcodeClass::Unknown,
+        }
+    }
+}
+
+// Which instructions get traced - `None` lets everything through
+#[derive(Debug, Clone, Default)]
+pub struct TraceFilter {
+    pub addresses: Option<RangeInclusive<u16>>,
+    pub classes: Option<Vec<OpcodeClass>>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, pc: u16, instruction: &Instruction) -> bool {
+        let address_matches = match &self.addresses {
+            Some(range) => range.contains(&pc),
+            None => true,
+        };
+        let class_matches = match &self.classes {
+            Some(classes) => classes.contains(&instruction.class()),
+            None => true,
+        };
+        address_matches && class_matches
+    }
+}
+
+// Registers that can be compared before and after an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RegisterSnapshot {
+    pub v_registers: [u8; 16],
+    pub i_register: u16,
+    pub stack_pointer: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl RegisterSnapshot {
+    // Lists every register whose value differs - (name, old value, new value)
+    pub fn changes(&self, after: &RegisterSnapshot) -> Vec<(String, u16, u16)> {
+        let mut changes = Vec::new();
+        for (register, (old, new)) in self
+            .v_registers
+            .iter()
+            .zip(after.v_registers.iter())
+            .enumerate()
+        {
+            if old != new {
+                changes.push((format!("V{:X}", register), *old as u16, *new as u16));
+            }
+        }
+        let others = [
+            ("I", self.i_register, after.i_register),
+            ("SP", self.stack_pointer, after.stack_pointer),
+            ("DT", self.delay_timer as u16, after.delay_timer as u16),
+            ("ST", self.sound_timer as u16, after.sound_timer as u16),
+        ];
+        for (name, old, new) in others {
+            if old != new {
+                changes.push((String::from(name), old, new));
+            }
+        }
+        changes
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    pub changes: Vec<(String, u16, u16)>,
+}
+
+impl TraceEntry {
+    pub fn write(&self, writer: &mut impl Write, format: TraceFormat) -> io::Result<()> {
+        match format {
+            TraceFormat::Text => {
+                let changes: Vec<String> = self
+                    .changes
+                    .iter()
+                    .map(|(name, old, new)| format!("{}: {:X} -> {:X}", name, old, new))
+                    .collect();
+                writeln!(
+                    writer,
+                    "{:04X}  {:04X}  {:<32} {}",
+                    self.pc,
+                    self.opcode,
+                    format!("{:?}", self.instruction),
+                    changes.join(", ")
+                )
+            }
+            TraceFormat::JsonLines => {
+                let changes: Vec<String> = self
+                    .changes
+                    .iter()
+                    .map(|(name, old, new)| format!("\"{}\":[{},{}]", name, old, new))
+                    .collect();
+                writeln!(
+                    writer,
+                    "{{\"pc\":{},\"opcode\":\"{:04X}\",\"instruction\":\"{:?}\",\"changes\":{{{}}}}}",
+                    self.pc,
+                    self.opcode,
+                    self.instruction,
+                    changes.join(",")
+                )
+            }
+        }
+    }
+}
+
+pub enum TraceOutput {
+    // Every traced instruction is written straight away
+    Writer(Box<dyn Write>),
+    // Only the most recent N instructions are kept in memory
+    RingBuffer(usize),
+}
+
+pub struct Tracer {
+    filter: TraceFilter,
+    format: TraceFormat,
+    writer: Option<Box<dyn Write>>,
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+}
+
+impl Tracer {
+    pub fn new(output: TraceOutput, format: TraceFormat, filter: TraceFilter) -> Self {
+        let (writer, capacity) = match output {
+            TraceOutput::Writer(writer) => (Some(writer), 0),
+            TraceOutput::RingBuffer(capacity) => (None, capacity),
+        };
+        Self {
+            filter,
+            format,
+            writer,
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    // Writes the trace to a new file at `path`
+    #[cfg(feature = "fs")]
+    pub fn to_file(path: &Path, format: TraceFormat, filter: TraceFilter) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Self::new(
+            TraceOutput::Writer(Box::new(file)),
+            format,
+            filter,
+        ))
+    }
+
+    pub fn wants(&self, pc: u16, instruction: &Instruction) -> bool {
+        self.filter.matches(pc, instruction)
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) -> io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => entry.write(writer, self.format),
+            None => {
+                if self.capacity == 0 {
+                    return Ok(());
+                }
+                if self.entries.len() == self.capacity {
+                    self.entries.pop_front();
+                }
+                self.entries.push_back(entry);
+                Ok(())
+            }
+        }
+    }
+
+    // Instructions held by the ring buffer, oldest first
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    // Writes out everything held by the ring buffer, oldest first
+    pub fn dump(&self, writer: &mut impl Write) -> io::Result<()> {
+        for entry in &self.entries {
+            entry.write(writer, self.format)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
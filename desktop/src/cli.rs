@@ -0,0 +1,320 @@
+// Command line parsing for the desktop binary
+
+use chip8_core::{
+    Engine, MemoryMap, OpcodeClass, Palette, Platform, Quirks, RecordingOptions, Timing,
+    TraceFilter, TraceFormat, FRAMES_PER_SECOND, MAX_STACK_DEPTH,
+};
+
+const DEFAULT_ROM_DIR: &str = "roms";
+
+pub const HELP: &str = "\
+Chip-8 Emulator
+
+USAGE:
+    desktop <COMMAND> <ROM> [OPTIONS]
+    desktop <ROM> [OPTIONS]            Same as `desktop run <ROM>`
+    desktop browse [DIR] [OPTIONS]     Pick a ROM from DIR (default: roms)
+    desktop bench [ROM|DIR] [OPTIONS]  Benchmark a ROM, or every ROM in DIR (default: roms)
+
+COMMANDS:
+    run      Play a ROM in a window, or pick one from a directory
+    browse   Pick a ROM to play from a directory, Escape goes back to the list
+    disasm   Print the ROM as CHIP-8 assembly
+    info     Show the ROM's size, SHA-1 and ROM database entry
+    bench    Run ROMs without a window with a fixed key script and report their speed
+    test     Run a ROM without a window and print the final screen
+    help     Show this text
+
+EMULATION OPTIONS (run, browse, bench, test):
+    --engine <ENGINE>        interpreter, or blocks to run cached basic blocks, which is
+                             faster and gives the same results (default: interpreter)
+    --quirks <PRESET>        Quirks to use: chip8, schip or xochip
+                             (default: from the ROM database)
+    --ips <N>                Instructions per second (default: from the ROM database, or 600)
+    --ipf <N>                Instructions per 60 Hz frame, instead of --ips
+    --memory-map <MAP>       flat, or vip to keep the screen, stack and registers in RAM at
+                             0xEA0-0xFFF like the COSMAC VIP (default: flat)
+    --stack-depth <N>        Nested subroutine calls allowed, up to 64 (default: from the
+                             quirks, 12 for chip8 and 16 otherwise)
+    --timing <MODEL>         ipf for a fixed number of instructions per frame, or vip to
+                             charge each instruction its COSMAC VIP machine cycles, which
+                             runs games at their original speed (default: ipf)
+    --seed <N>               Seed for the random number instruction, makes runs repeatable
+                             (bench: 1)
+
+WINDOW OPTIONS (run, browse):
+    --scale <N>              Size of a CHIP-8 pixel in the window (default: 20)
+    --palette <PALETTE>      classic, amber, green, arcade, or background:foreground in hex,
+                             e.g. 000000:33FF33 (default: from the ROM database)
+    --keymap <FILE>          Keymap file with lines like `A = Z` (CHIP-8 key = keyboard key)
+    --mute                   Turn the beeper off
+    --debugger               Start paused in the terminal debugger
+    --stats                  Show the FPS / IPS / frame time panel (toggle with F3)
+    --fast-forward <N>       Speed multiplier while Tab is held (default: 4)
+    --screenshot-scale <N>   Size of a CHIP-8 pixel in screenshots (default: 1)
+    --record-raw             Recordings also dump raw RGB24 frames
+    --record-audio           Recordings also write a WAV audio track
+
+TRACE OPTIONS (run, bench, test):
+    --trace <FILE>           Write every instruction to FILE
+    --trace-last <N>         Keep the last N instructions and print them if the emulator crashes
+    --trace-format <FORMAT>  text or json (default: text)
+    --trace-range <A-B>      Only trace addresses A to B, in hex, e.g. 200-2FF
+    --trace-class <LIST>     Only trace these classes, comma separated: flow, skip,
+                             arithmetic, memory, display, input, timer
+
+RUN OPTIONS (bench, test):
+    --frames <N>             Number of 60 Hz frames to run (default: 600)
+    --expect <SHA1>          test: fail unless the final screen has this SHA-1
+
+HOTKEYS:
+    Esc   Back to the ROM list (browse)
+    Tab   Fast-forward while held
+    F1    Break into the debugger (with --debugger)
+    F3    Show / hide the statistics panel
+    F5    Reset - restart the game with cleared memory
+    F6    Soft reset - jump back to the start of the program, keeping memory
+    F7    Pause / resume, timers stop while paused
+    F8    Slow motion on / off
+    F10   Start / stop recording a GIF
+    F12   Save a PNG screenshot
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Run,
+    Browse,
+    Disasm,
+    Info,
+    Bench,
+    Test,
+    Help,
+}
+
+pub enum TraceDestination {
+    File(String),
+    RingBuffer(usize),
+}
+
+pub struct Options {
+    pub command: Command,
+    pub rom: Option<String>,
+
+    pub quirks: Option<Quirks>,
+    pub instructions_per_frame: Option<u32>,
+    pub stack_depth: Option<usize>,
+    pub memory_map: MemoryMap,
+    pub timing: Timing,
+    pub engine: Engine,
+    pub seed: Option<u64>,
+
+    pub scale: u32,
+    pub palette: Option<Palette>,
+    pub keymap: Option<String>,
+    pub mute: bool,
+    pub debugger: bool,
+    pub fast_forward: u32,
+    pub stats: bool,
+    pub screenshot_scale: usize,
+    pub recording: RecordingOptions,
+
+    pub trace: Option<TraceDestination>,
+    pub trace_format: TraceFormat,
+    pub trace_filter: TraceFilter,
+
+    pub frames: u32,
+    pub expect: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            command: Command::Run,
+            rom: None,
+            quirks: None,
+            instructions_per_frame: None,
+            stack_depth: None,
+            memory_map: MemoryMap::Flat,
+            timing: Timing::InstructionsPerFrame,
+            engine: Engine::Interpreter,
+            seed: None,
+            scale: 20,
+            palette: None,
+            keymap: None,
+            mute: false,
+            debugger: false,
+            fast_forward: 4,
+            stats: false,
+            screenshot_scale: 1,
+            recording: RecordingOptions::default(),
+            trace: None,
+            trace_format: TraceFormat::Text,
+            trace_filter: TraceFilter::default(),
+            frames: 600,
+            expect: None,
+        }
+    }
+}
+
+// Parses the arguments after the program name
+pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    match args.next().map(String::as_str) {
+        None | Some("help") | Some("--help") | Some("-h") => {
+            options.command = Command::Help;
+            return Ok(options);
+        }
+        Some("run") => options.command = Command::Run,
+        Some("browse") => options.command = Command::Browse,
+        Some("disasm") => options.command = Command::Disasm,
+        Some("info") => options.command = Command::Info,
+        Some("bench") => options.command = Command::Bench,
+        Some("test") => options.command = Command::Test,
+        Some(option) if option.starts_with("--") => {
+            return Err(format!("expected a command or a ROM before {}", option))
+        }
+        Some(rom) => options.rom = Some(String::from(rom)),
+    }
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", name))
+        };
+        match arg.as_str() {
+            "--help" | "-h" => options.command = Command::Help,
+            "--quirks" => {
+                let name = value(arg)?;
+                let platform = Platform::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unknown quirks preset '{}' (use chip8, schip or xochip)",
+                        name
+                    )
+                })?;
+                options.quirks = Some(platform.quirks());
+            }
+            "--ips" => {
+                let ips: u32 = parse_number(arg, &value(arg)?)?;
+                options.instructions_per_frame = Some((ips / FRAMES_PER_SECOND).max(1));
+            }
+            "--ipf" => options.instructions_per_frame = Some(parse_number(arg, &value(arg)?)?),
+            "--timing" => {
+                let name = value(arg)?;
+                options.timing = Timing::from_name(&name)
+                    .ok_or_else(|| format!("unknown timing model '{}' (use ipf or vip)", name))?;
+            }
+            "--engine" => {
+                let name = value(arg)?;
+                options.engine = Engine::from_name(&name).ok_or_else(|| {
+                    format!("unknown engine '{}' (use interpreter or blocks)", name)
+                })?;
+            }
+            "--memory-map" => {
+                let name = value(arg)?;
+                options.memory_map = MemoryMap::from_name(&name)
+                    .ok_or_else(|| format!("unknown memory map '{}' (use flat or vip)", name))?;
+            }
+            "--stack-depth" => {
+                let depth: usize = parse_number(arg, &value(arg)?)?;
+                if !(1..=MAX_STACK_DEPTH).contains(&depth) {
+                    return Err(format!(
+                        "--stack-depth must be between 1 and {}",
+                        MAX_STACK_DEPTH
+                    ));
+                }
+                options.stack_depth = Some(depth);
+            }
+            "--seed" => options.seed = Some(parse_number(arg, &value(arg)?)?),
+            "--scale" => options.scale = parse_number::<u32>(arg, &value(arg)?)?.max(1),
+            "--palette" => {
+                let text = value(arg)?;
+                options.palette = Some(
+                    Palette::parse(&text).ok_or_else(|| format!("unknown palette '{}'", text))?,
+                );
+            }
+            "--keymap" => options.keymap = Some(value(arg)?),
+            "--mute" => options.mute = true,
+            "--debugger" => options.debugger = true,
+            "--stats" => options.stats = true,
+            "--fast-forward" => {
+                options.fast_forward = parse_number::<u32>(arg, &value(arg)?)?.max(1)
+            }
+            "--screenshot-scale" => {
+                options.screenshot_scale = parse_number::<usize>(arg, &value(arg)?)?.max(1)
+            }
+            "--record-raw" => options.recording.raw_frames = true,
+            "--record-audio" => options.recording.audio = true,
+            "--trace" => options.trace = Some(TraceDestination::File(value(arg)?)),
+            "--trace-last" => {
+                options.trace = Some(TraceDestination::RingBuffer(parse_number(
+                    arg,
+                    &value(arg)?,
+                )?))
+            }
+            "--trace-format" => {
+                options.trace_format = match value(arg)?.as_str() {
+                    "text" => TraceFormat::Text,
+                    "json" | "jsonl" => TraceFormat::JsonLines,
+                    other => return Err(format!("unknown trace format '{}'", other)),
+                }
+            }
+            "--trace-range" => {
+                let text = value(arg)?;
+                let range = text
+                    .split_once('-')
+                    .and_then(|(start, end)| {
+                        Some(
+                            u16::from_str_radix(start, 16).ok()?
+                                ..=u16::from_str_radix(end, 16).ok()?,
+                        )
+                    })
+                    .ok_or_else(|| {
+                        format!("bad address range '{}', expected e.g. 200-2FF", text)
+                    })?;
+                options.trace_filter.addresses = Some(range);
+            }
+            "--trace-class" => {
+                let classes = value(arg)?
+                    .split(',')
+                    .map(parse_class)
+                    .collect::<Result<Vec<_>, _>>()?;
+                options.trace_filter.classes = Some(classes);
+            }
+            "--frames" => options.frames = parse_number(arg, &value(arg)?)?,
+            "--expect" => options.expect = Some(value(arg)?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if options.rom.is_none() => options.rom = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    if matches!(options.command, Command::Browse | Command::Bench) && options.rom.is_none() {
+        options.rom = Some(String::from(DEFAULT_ROM_DIR));
+    }
+    if options.command != Command::Help && options.rom.is_none() {
+        return Err(String::from("no ROM given"));
+    }
+    Ok(options)
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} needs a number, got '{}'", option, value))
+}
+
+fn parse_class(name: &str) -> Result<OpcodeClass, String> {
+    match name.trim() {
+        "flow" => Ok(OpcodeClass::Flow),
+        "skip" => Ok(OpcodeClass::Skip),
+        "arithmetic" => Ok(OpcodeClass::Arithmetic),
+        "memory" => Ok(OpcodeClass::Memory),
+        "display" => Ok(OpcodeClass::Display),
+        "input" => Ok(OpcodeClass::Input),
+        "timer" => Ok(OpcodeClass::Timer),
+        other => Err(format!("unknown opcode class '{}'", other)),
+    }
+}
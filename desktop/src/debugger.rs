@@ -0,0 +1,205 @@
+// A small terminal debugger - the emulator pauses and commands are read from stdin
+
+use chip8_core::{Emulator, Instruction, RAM_SIZE};
+use std::io::{self, Write};
+
+const HELP: &str = "\
+    s [N]         Step N instructions (default 1)
+    c             Continue
+    r             Show registers and timers
+    bt            Show the call stack
+    b <ADDR>      Set a breakpoint at ADDR (hex)
+    d <ADDR>      Delete the breakpoint at ADDR
+    l             List breakpoints
+    u [ADDR]      Disassemble 10 instructions from ADDR (default PC)
+    m <ADDR> [N]  Show N bytes of memory from ADDR (default 64)
+    q             Quit the emulator
+    h             Show this help";
+
+pub enum DebuggerAction {
+    Continue,
+    Quit,
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<u16>,
+    paused: bool,
+
+    // Set when execution resumes, so the breakpoint we stopped on does not fire straight away
+    resuming: bool,
+}
+
+impl Debugger {
+    // Starts paused so breakpoints can be set before the game runs
+    pub fn new() -> Self {
+        Self {
+            paused: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    // Asked before every instruction while running
+    pub fn should_break(&mut self, emu: &Emulator) -> bool {
+        if self.resuming {
+            self.resuming = false;
+            return false;
+        }
+        if self.breakpoints.contains(&emu.pc()) {
+            println!("Breakpoint at {:03X}", emu.pc());
+            self.paused = true;
+            return true;
+        }
+        false
+    }
+
+    // Reads commands until the user continues or quits
+    pub fn prompt(&mut self, emu: &mut Emulator) -> DebuggerAction {
+        print_current(emu);
+        loop {
+            print!("(chip8) ");
+            io::stdout().flush().ok();
+
+            let mut line = String::new();
+            match io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => return DebuggerAction::Quit,
+                Ok(_) => {}
+            }
+            let mut words = line.split_whitespace();
+            let command = words.next().unwrap_or("s");
+            let argument = words.next();
+
+            match command {
+                "s" | "step" => {
+                    let count = argument.and_then(|n| n.parse().ok()).unwrap_or(1);
+                    for _ in 0..count {
+                        if let Err(e) = emu.step() {
+                            println!("{}", e);
+                            break;
+                        }
+                    }
+                    print_current(emu);
+                }
+                "c" | "continue" => {
+                    self.paused = false;
+                    self.resuming = true;
+                    return DebuggerAction::Continue;
+                }
+                "r" | "registers" => print_registers(emu),
+                "bt" | "backtrace" => print_backtrace(emu),
+                "b" | "break" => match parse_address(argument) {
+                    Some(address) => {
+                        if !self.breakpoints.contains(&address) {
+                            self.breakpoints.push(address);
+                        }
+                        println!("Breakpoint set at {:03X}", address);
+                    }
+                    None => println!("Usage: b <ADDR>"),
+                },
+                "d" | "delete" => match parse_address(argument) {
+                    Some(address) => self.breakpoints.retain(|b| *b != address),
+                    None => println!("Usage: d <ADDR>"),
+                },
+                "l" | "list" => {
+                    for address in &self.breakpoints {
+                        println!("{:03X}", address);
+                    }
+                }
+                "u" | "disasm" => {
+                    let start = parse_address(argument).unwrap_or(emu.pc());
+                    for address in (start..).step_by(2).take(10) {
+                        print_instruction(emu, address);
+                    }
+                }
+                "m" | "memory" => match parse_address(argument) {
+                    Some(address) => {
+                        let length = words.next().and_then(|n| n.parse().ok()).unwrap_or(64);
+                        print_memory(emu, address as usize, length);
+                    }
+                    None => println!("Usage: m <ADDR> [N]"),
+                },
+                "q" | "quit" => return DebuggerAction::Quit,
+                "h" | "help" => println!("{}", HELP),
+                _ => println!("Unknown command, h for help"),
+            }
+        }
+    }
+}
+
+fn parse_address(argument: Option<&str>) -> Option<u16> {
+    u16::from_str_radix(argument?.trim_start_matches("0x"), 16)
+        .ok()
+        .filter(|address| (*address as usize) < RAM_SIZE - 1)
+}
+
+fn print_current(emu: &Emulator) {
+    print_instruction(emu, emu.pc());
+}
+
+fn print_instruction(emu: &Emulator, address: u16) {
+    let memory = emu.memory();
+    let address = address as usize % (RAM_SIZE - 1);
+    let opcode = ((memory[address] as u16) << 8) | memory[address + 1] as u16;
+    let marker = if address == emu.pc() as usize {
+        ">"
+    } else {
+        " "
+    };
+    println!(
+        "{} {:03X}: {:04X}  {}",
+        marker,
+        address,
+        opcode,
+        Instruction::decode(opcode)
+    );
+}
+
+fn print_registers(emu: &Emulator) {
+    for (register, value) in emu.v_registers().iter().enumerate() {
+        print!("V{:X}={:02X} ", register, value);
+        if register % 8 == 7 {
+            println!();
+        }
+    }
+    println!(
+        "PC={:03X} I={:03X} DT={:02X} ST={:02X}",
+        emu.pc(),
+        emu.i_register(),
+        emu.delay_timer(),
+        emu.sound_timer()
+    );
+}
+
+// Innermost frame first - each caller is shown at the 2NNN that made the call
+fn print_backtrace(emu: &Emulator) {
+    println!("#0  {:03X}", emu.pc());
+    for (frame, return_address) in emu.call_stack().iter().rev().enumerate() {
+        println!(
+            "#{:<2} {:03X}  (returns to {:03X})",
+            frame + 1,
+            return_address.wrapping_sub(2),
+            return_address
+        );
+    }
+    println!(
+        "{} of {} stack levels in use",
+        emu.call_stack().len(),
+        emu.quirks().stack_depth
+    );
+}
+
+fn print_memory(emu: &Emulator, start: usize, length: usize) {
+    let end = (start + length).min(RAM_SIZE);
+    for (row, bytes) in emu.memory()[start..end].chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        println!("{:03X}: {}", start + row * 16, hex.join(" "));
+    }
+}
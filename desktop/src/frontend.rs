@@ -0,0 +1,446 @@
+// SDL frontend - window, keyboard, beeper and hotkeys around the emulator core
+
+use crate::{
+    debugger::{Debugger, DebuggerAction},
+    keymap::Keymap,
+    launcher::Launcher,
+    osd::Osd,
+    speed::SpeedControl,
+};
+use chip8_core::{
+    DirtyRect, Emulator, EmulatorError, RecordingOptions, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+use sdl2::{
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Scancode},
+    pixels::PixelFormatEnum,
+    rect::Rect,
+    render::{Canvas, Texture},
+    video::Window,
+    EventPump,
+};
+use std::{
+    io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    thread, time,
+};
+
+const BEEP_HZ: f32 = 440.0;
+const BEEP_VOLUME: f32 = 0.15;
+
+pub struct FrontendOptions {
+    pub title: String,
+    pub scale: u32,
+    pub keymap: Keymap,
+    pub mute: bool,
+    pub debugger: bool,
+    pub screenshot_scale: usize,
+    pub recording: RecordingOptions,
+
+    // Escape goes back to the ROM launcher
+    pub launcher: bool,
+
+    // Emulated frames per real frame while fast-forward is held
+    pub fast_forward: u32,
+
+    // Start with the statistics panel shown
+    pub stats: bool,
+}
+
+struct SquareWave {
+    phase_increment: f32,
+    phase: f32,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = if self.phase < 0.5 {
+                BEEP_VOLUME
+            } else {
+                -BEEP_VOLUME
+            };
+            self.phase = (self.phase + self.phase_increment) % 1.0;
+        }
+    }
+}
+
+// How a game stopped playing
+pub enum Exit {
+    Quit,
+    Launcher,
+}
+
+// The window, renderer, input and beeper - kept for the whole process, so games can be
+// switched without tearing SDL down
+pub struct Frontend {
+    options: FrontendOptions,
+    _sdl_context: sdl2::Sdl,
+    canvas: Canvas<Window>,
+    event_pump: EventPump,
+    beeper: Option<AudioDevice<SquareWave>>,
+}
+
+impl Frontend {
+    pub fn new(options: FrontendOptions) -> Result<Self, String> {
+        let sdl_context = sdl2::init()?;
+        let video_subsystem = sdl_context.video()?;
+        let window = video_subsystem
+            .window(
+                &options.title,
+                SCREEN_WIDTH as u32 * options.scale,
+                SCREEN_HEIGHT as u32 * options.scale,
+            )
+            .position_centered()
+            .build()
+            .map_err(|e| e.to_string())?;
+        let canvas = window
+            .into_canvas()
+            .accelerated()
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let beeper = if options.mute {
+            None
+        } else {
+            open_beeper(&sdl_context)
+                .map_err(|e| eprintln!("Sound is off: {}", e))
+                .ok()
+        };
+        let event_pump = sdl_context.event_pump()?;
+
+        Ok(Self {
+            options,
+            _sdl_context: sdl_context,
+            canvas,
+            event_pump,
+            beeper,
+        })
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.canvas.window_mut().set_title(title).ok();
+    }
+
+    // Shows the ROM launcher until a ROM is picked, None when the window is closed
+    pub fn choose_rom(&mut self, launcher: &mut Launcher) -> Option<PathBuf> {
+        launcher.choose(&mut self.canvas, &mut self.event_pump)
+    }
+
+    // Plays the game until the window is closed, or Escape is pressed when there is a
+    // launcher to go back to
+    pub fn play(&mut self, emu: &mut Emulator) -> Result<Exit, String> {
+        let texture_creator = self.canvas.texture_creator();
+        let mut texture = texture_creator
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                SCREEN_WIDTH as u32,
+                SCREEN_HEIGHT as u32,
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut debugger = if self.options.debugger {
+            println!("Debugger started, h for help");
+            Some(Debugger::new())
+        } else {
+            None
+        };
+
+        let mut speed = SpeedControl::new(self.options.fast_forward);
+        let mut osd = Osd::new(self.options.stats);
+        let mut osd_shown = false;
+
+        let mut redraw = true;
+        let mut next_frame = time::Instant::now();
+
+        let result = loop {
+            let keyboard = self.event_pump.keyboard_state();
+            emu.set_keys(self.options.keymap.pressed_keys(&keyboard));
+            speed.set_fast_forward(keyboard.is_scancode_pressed(Scancode::Tab));
+
+            let mut exit = None;
+            for event in self.event_pump.poll_iter() {
+                match event {
+                    Event::Quit { .. } => exit = Some(Exit::Quit),
+                    Event::Window {
+                        win_event: WindowEvent::Exposed | WindowEvent::SizeChanged(..),
+                        ..
+                    } => redraw = true,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        repeat: false,
+                        ..
+                    } if self.options.launcher => exit = Some(Exit::Launcher),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F1),
+                        repeat: false,
+                        ..
+                    } => {
+                        if let Some(debugger) = &mut debugger {
+                            debugger.pause();
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F5),
+                        repeat: false,
+                        ..
+                    } => {
+                        emu.reset();
+                        redraw = true;
+                        osd.show("Reset");
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F6),
+                        repeat: false,
+                        ..
+                    } => {
+                        emu.soft_reset();
+                        osd.show("Soft reset");
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F7 | Keycode::Pause),
+                        repeat: false,
+                        ..
+                    } => speed.toggle_pause(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F8),
+                        repeat: false,
+                        ..
+                    } => speed.toggle_slow_motion(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F3),
+                        repeat: false,
+                        ..
+                    } => osd.toggle_stats(),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F10),
+                        repeat: false,
+                        ..
+                    } => toggle_recording(emu, self.options.recording, &mut osd),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F12),
+                        repeat: false,
+                        ..
+                    } => match emu.save_screenshot(Path::new("."), self.options.screenshot_scale) {
+                        Ok(path) => {
+                            println!("Saved screenshot to {}", path.display());
+                            osd.show("Screenshot saved");
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to save screenshot: {}", e);
+                            osd.show("Screenshot failed");
+                        }
+                    },
+                    _ => {}
+                }
+            }
+            if let Some(exit) = exit {
+                break Ok(exit);
+            }
+
+            if let Some(debugger) = &mut debugger {
+                if debugger.is_paused() {
+                    if let Some(beeper) = &self.beeper {
+                        beeper.pause();
+                    }
+                    if let DebuggerAction::Quit = debugger.prompt(emu) {
+                        break Ok(Exit::Quit);
+                    }
+                    redraw = true;
+                    next_frame = time::Instant::now();
+                }
+            }
+
+            let work_start = time::Instant::now();
+
+            // While fast-forwarding several frames run back to back and only the last is drawn
+            let frames = speed.frames_per_tick();
+            let frame = panic::catch_unwind(AssertUnwindSafe(|| {
+                for _ in 0..frames {
+                    match &mut debugger {
+                        Some(debugger) => {
+                            if emu.run_frame_until(|emu| debugger.should_break(emu))? {
+                                break;
+                            }
+                        }
+                        None => emu.run_frame()?,
+                    }
+                }
+                Ok::<(), EmulatorError>(())
+            }));
+            match frame {
+                Ok(Ok(())) => {}
+                // With the debugger the fault can be looked at, PC is left on the instruction
+                Ok(Err(fault)) => match &mut debugger {
+                    Some(debugger) => {
+                        println!("{}", fault);
+                        debugger.pause();
+                        continue;
+                    }
+                    None => {
+                        dump_trace(emu);
+                        break Err(fault.to_string());
+                    }
+                },
+                Err(crash) => {
+                    dump_trace(emu);
+                    panic::resume_unwind(crash);
+                }
+            }
+
+            if let Some(beeper) = &self.beeper {
+                if emu.sound_active() && !speed.is_paused() {
+                    beeper.resume();
+                } else {
+                    beeper.pause();
+                }
+            }
+
+            osd.set_status(
+                speed
+                    .label()
+                    .map(|label| format!("{}  {:.0} IPS", label, osd.ips())),
+            );
+            // The OSD has to be painted over once it goes away
+            let osd_visible = osd.is_visible();
+            let force = redraw || (osd_shown && !osd_visible);
+            update_screen(
+                emu,
+                &mut self.canvas,
+                &mut texture,
+                force,
+                osd_visible.then_some(&osd),
+            );
+            osd_shown = osd_visible;
+            redraw = false;
+            osd.record_frame(
+                emu.instruction_count(),
+                work_start.elapsed(),
+                emu.sound_active(),
+            );
+
+            // Sleep until the next frame is due - if we fell behind, carry on from now instead
+            // of rushing through the missed frames
+            next_frame += speed.tick_duration();
+            let now = time::Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                next_frame = now;
+            }
+        };
+
+        if let Some(beeper) = &self.beeper {
+            beeper.pause();
+        }
+        finish(emu);
+        result
+    }
+}
+
+fn open_beeper(sdl_context: &sdl2::Sdl) -> Result<AudioDevice<SquareWave>, String> {
+    let audio_subsystem = sdl_context.audio()?;
+    let desired = AudioSpecDesired {
+        freq: Some(44100),
+        channels: Some(1),
+        samples: None,
+    };
+    audio_subsystem.open_playback(None, &desired, |spec| SquareWave {
+        phase_increment: BEEP_HZ / spec.freq as f32,
+        phase: 0.0,
+    })
+}
+
+// Uploads the changed part of the screen to the texture and redraws the window, with the
+// OSD on top
+// Does nothing when the screen has not changed and there is no OSD, unless `force` is set
+fn update_screen(
+    emu: &mut Emulator,
+    canvas: &mut Canvas<Window>,
+    texture: &mut Texture,
+    force: bool,
+    osd: Option<&Osd>,
+) {
+    let region = match emu.take_dirty_region() {
+        Some(region) => Some(region),
+        None if force => Some(DirtyRect::FULL_SCREEN),
+        None => None,
+    };
+    if region.is_none() && osd.is_none() {
+        return;
+    }
+    if let Some(region) = region {
+        let rect = Rect::new(
+            region.x as i32,
+            region.y as i32,
+            region.width as u32,
+            region.height as u32,
+        );
+        texture
+            .update(rect, &emu.region_to_rgb(region), region.width * 3)
+            .unwrap();
+    }
+    canvas.copy(texture, None, None).unwrap();
+    if let Some(osd) = osd {
+        osd.draw(canvas);
+    }
+    canvas.present();
+}
+
+fn toggle_recording(emu: &mut Emulator, options: RecordingOptions, osd: &mut Osd) {
+    if emu.is_recording() {
+        finish_recording(emu);
+        osd.show("Recording saved");
+    } else {
+        match emu.start_recording(Path::new("."), options) {
+            Ok(()) => {
+                println!("Recording started");
+                osd.show("Recording started");
+            }
+            Err(e) => {
+                eprintln!("Failed to start recording: {}", e);
+                osd.show("Recording failed");
+            }
+        }
+    }
+}
+
+fn finish_recording(emu: &mut Emulator) {
+    match emu.stop_recording() {
+        Ok(paths) => {
+            for path in paths {
+                println!("Saved recording to {}", path.display());
+            }
+        }
+        Err(e) => eprintln!("Failed to save recording: {}", e),
+    }
+}
+
+// Saves anything still in progress before the window closes
+fn finish(emu: &mut Emulator) {
+    if emu.is_recording() {
+        finish_recording(emu);
+    }
+    if let Err(e) = emu.flush_trace() {
+        eprintln!("Failed to write trace: {}", e);
+    }
+}
+
+// Prints the instructions kept by a ring buffer tracer after a crash
+fn dump_trace(emu: &mut Emulator) {
+    if let Some(tracer) = emu.tracer() {
+        if tracer.entries().next().is_some() {
+            eprintln!("Last traced instructions:");
+            if let Err(e) = tracer.dump(&mut io::stderr()) {
+                eprintln!("Failed to dump trace: {}", e);
+            }
+        }
+    }
+    if let Err(e) = emu.flush_trace() {
+        eprintln!("Failed to write trace: {}", e);
+    }
+}
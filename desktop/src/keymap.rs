@@ -0,0 +1,80 @@
+// Which keyboard key drives each of the 16 CHIP-8 keys
+//
+// Keymap files hold one `CHIP-8 key = keyboard key` pair per line, using SDL key names, e.g.
+//     # Put 0 on the space bar
+//     0 = Space
+// Keys that are not listed keep their default
+
+use chip8_core::NUM_KEYS;
+use sdl2::keyboard::{KeyboardState, Scancode};
+use std::fs;
+
+pub struct Keymap {
+    scancodes: [Scancode; NUM_KEYS],
+}
+
+impl Default for Keymap {
+    // The usual layout - the left side of a QWERTY keyboard mirrors the COSMAC VIP keypad
+    //     1 2 3 C        1 2 3 4
+    //     4 5 6 D   ->   Q W E R
+    //     7 8 9 E        A S D F
+    //     A 0 B F        Z X C V
+    fn default() -> Self {
+        Self {
+            scancodes: [
+                Scancode::X,
+                Scancode::Num1,
+                Scancode::Num2,
+                Scancode::Num3,
+                Scancode::Q,
+                Scancode::W,
+                Scancode::E,
+                Scancode::A,
+                Scancode::S,
+                Scancode::D,
+                Scancode::Z,
+                Scancode::C,
+                Scancode::Num4,
+                Scancode::R,
+                Scancode::F,
+                Scancode::V,
+            ],
+        }
+    }
+}
+
+impl Keymap {
+    pub fn load(path: &str) -> Result<Keymap, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("could not read keymap '{}': {}", path, e))?;
+
+        let mut keymap = Keymap::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |message: &str| format!("{} line {}: {}", path, number + 1, message);
+            let (key, name) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected `CHIP-8 key = keyboard key`"))?;
+            let key = u8::from_str_radix(key.trim(), 16)
+                .ok()
+                .filter(|key| (*key as usize) < NUM_KEYS)
+                .ok_or_else(|| error("CHIP-8 keys are 0 to F"))?;
+            let scancode = Scancode::from_name(name.trim())
+                .ok_or_else(|| error(&format!("unknown keyboard key '{}'", name.trim())))?;
+            keymap.scancodes[key as usize] = scancode;
+        }
+        Ok(keymap)
+    }
+
+    pub fn pressed_keys(&self, keyboard: &KeyboardState) -> [bool; NUM_KEYS] {
+        let mut keys = [false; NUM_KEYS];
+        for (key, scancode) in self.scancodes.iter().enumerate() {
+            keys[key] = keyboard.is_scancode_pressed(*scancode);
+        }
+        keys
+    }
+}
@@ -0,0 +1,303 @@
+// ROM launcher - a menu of the ROMs in a directory, drawn in the emulator window
+
+use crate::font::{self, LINE_HEIGHT};
+use chip8_core::{romdb, MAX_ROM_SIZE};
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::Keycode,
+    pixels::Color,
+    rect::Rect,
+    render::Canvas,
+    video::Window,
+    EventPump,
+};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+const MAX_RECENT: usize = 8;
+
+const BACKGROUND: Color = Color::RGB(0, 0, 0);
+const TEXT: Color = Color::RGB(220, 220, 220);
+const DIM: Color = Color::RGB(120, 120, 120);
+const HEADING: Color = Color::RGB(255, 176, 0);
+const HIGHLIGHT: Color = Color::RGB(60, 60, 140);
+
+pub struct RomEntry {
+    pub path: PathBuf,
+    pub title: String,
+
+    // Platform from the ROM database, or the file name for unknown ROMs
+    pub detail: String,
+}
+
+impl RomEntry {
+    // Reads the ROM to look it up in the ROM database, None if it is not a loadable ROM
+    pub fn read(path: &Path) -> Option<RomEntry> {
+        let rom = fs::read(path).ok()?;
+        if rom.is_empty() || rom.len() > MAX_ROM_SIZE {
+            return None;
+        }
+        let file_name = path.file_name()?.to_string_lossy().into_owned();
+        Some(match romdb::lookup(&rom) {
+            Some(info) => RomEntry {
+                path: path.to_path_buf(),
+                title: String::from(info.title),
+                detail: String::from(info.platform.name()),
+            },
+            None => RomEntry {
+                path: path.to_path_buf(),
+                title: file_name,
+                detail: String::from("unknown"),
+            },
+        })
+    }
+}
+
+// Every ROM file in `dir`, sorted by title
+pub fn scan(dir: &Path) -> io::Result<Vec<RomEntry>> {
+    let mut entries = Vec::new();
+    for file in fs::read_dir(dir)? {
+        let path = file?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if path.is_file() && !hidden {
+            entries.extend(RomEntry::read(&path));
+        }
+    }
+    entries.sort_by_key(|entry| entry.title.to_lowercase());
+    Ok(entries)
+}
+
+// Recently played ROMs, newest first, kept in the user's config directory between runs
+pub struct RecentRoms {
+    file: Option<PathBuf>,
+    paths: Vec<PathBuf>,
+}
+
+impl RecentRoms {
+    pub fn load() -> Self {
+        let file = recent_file();
+        let paths = file
+            .as_ref()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .map(|text| text.lines().map(PathBuf::from).collect())
+            .unwrap_or_default();
+        Self { file, paths }
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    // Moves `path` to the front of the list and saves it
+    pub fn add(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.paths.retain(|recent| *recent != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(MAX_RECENT);
+
+        if let Err(e) = self.save() {
+            eprintln!("Failed to save recent ROMs: {}", e);
+        }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let lines: Vec<String> = self
+            .paths
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        fs::write(file, lines.join("\n") + "\n")
+    }
+}
+
+// $XDG_CONFIG_HOME/chip8/recent, falling back to ~/.config/chip8/recent
+fn recent_file() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config.join("chip8").join("recent"))
+}
+
+enum Row {
+    Heading(&'static str),
+    Rom(RomEntry),
+}
+
+pub struct Launcher {
+    rows: Vec<Row>,
+
+    // Index into `rows` of the highlighted ROM, and the first row on screen
+    selected: usize,
+    scroll: usize,
+}
+
+impl Launcher {
+    // Lists the recently played ROMs above every ROM in `dir`
+    pub fn new(dir: &Path, recent: &RecentRoms) -> io::Result<Self> {
+        let mut rows = Vec::new();
+        let recent: Vec<RomEntry> = recent
+            .paths()
+            .iter()
+            .filter_map(|path| RomEntry::read(path))
+            .collect();
+        if !recent.is_empty() {
+            rows.push(Row::Heading("Recently played"));
+            rows.extend(recent.into_iter().map(Row::Rom));
+        }
+
+        let roms = scan(dir)?;
+        if roms.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no ROMs in {}", dir.display()),
+            ));
+        }
+        rows.push(Row::Heading("All ROMs"));
+        rows.extend(roms.into_iter().map(Row::Rom));
+
+        let mut launcher = Self {
+            rows,
+            selected: 0,
+            scroll: 0,
+        };
+        launcher.move_selection(1);
+        Ok(launcher)
+    }
+
+    // Shows the menu until a ROM is picked with Enter, None when the window is closed
+    // or Escape is pressed
+    pub fn choose(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        events: &mut EventPump,
+    ) -> Option<PathBuf> {
+        self.draw(canvas);
+        loop {
+            match events.wait_event() {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return None,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    let page = self.visible_rows(canvas) as isize - 1;
+                    match keycode {
+                        Keycode::Up => self.move_selection(-1),
+                        Keycode::Down => self.move_selection(1),
+                        Keycode::PageUp => self.move_selection(-page),
+                        Keycode::PageDown => self.move_selection(page),
+                        Keycode::Home => self.move_selection(-(self.rows.len() as isize)),
+                        Keycode::End => self.move_selection(self.rows.len() as isize),
+                        Keycode::Return | Keycode::KpEnter => {
+                            if let Row::Rom(entry) = &self.rows[self.selected] {
+                                return Some(entry.path.clone());
+                            }
+                        }
+                        _ => continue,
+                    }
+                    self.draw(canvas);
+                }
+                Event::Window {
+                    win_event: WindowEvent::Exposed | WindowEvent::SizeChanged(..),
+                    ..
+                } => self.draw(canvas),
+                _ => {}
+            }
+        }
+    }
+
+    // Moves the highlight by `delta` ROMs, skipping headings and stopping at either end
+    fn move_selection(&mut self, delta: isize) {
+        let step = delta.signum();
+        let mut remaining = delta.abs();
+        let mut index = self.selected as isize;
+        while remaining > 0 {
+            let next = index + step;
+            if next < 0 || next >= self.rows.len() as isize {
+                break;
+            }
+            index = next;
+            if let Row::Rom(_) = self.rows[index as usize] {
+                self.selected = index as usize;
+                remaining -= 1;
+            }
+        }
+        if delta < 0 && self.selected > 0 {
+            // Keep the heading above the first ROM of a section in view
+            if let Row::Heading(_) = self.rows[self.selected - 1] {
+                self.scroll = self.scroll.min(self.selected - 1);
+            }
+        }
+    }
+
+    // Font pixel size, so the menu fits roughly 24 lines whatever the window size
+    fn font_scale(canvas: &Canvas<Window>) -> u32 {
+        let (_, height) = canvas.output_size().unwrap_or((0, 0));
+        (height / (LINE_HEIGHT * 24)).max(1)
+    }
+
+    // Number of menu rows that fit below the title
+    fn visible_rows(&self, canvas: &Canvas<Window>) -> usize {
+        let (_, height) = canvas.output_size().unwrap_or((0, 0));
+        let line = LINE_HEIGHT * Self::font_scale(canvas);
+        ((height / line).saturating_sub(3) as usize).max(1)
+    }
+
+    fn draw(&mut self, canvas: &mut Canvas<Window>) {
+        let scale = Self::font_scale(canvas);
+        let line = (LINE_HEIGHT * scale) as i32;
+        let (width, _) = canvas.output_size().unwrap_or((0, 0));
+        let margin = (font::ADVANCE * scale) as i32;
+
+        // Scroll just enough to keep the selection on screen
+        let visible = self.visible_rows(canvas);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + visible {
+            self.scroll = self.selected + 1 - visible;
+        }
+
+        canvas.set_draw_color(BACKGROUND);
+        canvas.clear();
+        font::draw_text(canvas, "Chip-8 Emulator", margin, line / 2, scale, HEADING);
+        let help = "Up/Down  Enter play  Esc quit";
+        let help_x = width as i32 - margin - font::text_width(help, scale) as i32;
+        font::draw_text(canvas, help, help_x, line / 2, scale, DIM);
+
+        for (index, row) in self.rows.iter().enumerate().skip(self.scroll).take(visible) {
+            let y = line * (2 + (index - self.scroll) as i32);
+            let text_y = y + (line - (font::GLYPH_HEIGHT * scale) as i32) / 2;
+            match row {
+                Row::Heading(text) => {
+                    font::draw_text(canvas, text, margin, text_y, scale, HEADING);
+                }
+                Row::Rom(entry) => {
+                    if index == self.selected {
+                        canvas.set_draw_color(HIGHLIGHT);
+                        canvas
+                            .fill_rect(Rect::new(0, y, width, line as u32))
+                            .unwrap();
+                    }
+                    font::draw_text(canvas, &entry.title, margin * 3, text_y, scale, TEXT);
+                    let detail_x =
+                        width as i32 - margin - font::text_width(&entry.detail, scale) as i32;
+                    font::draw_text(canvas, &entry.detail, detail_x, text_y, scale, DIM);
+                }
+            }
+        }
+        canvas.present();
+    }
+}
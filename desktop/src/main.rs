@@ -1,8 +1,293 @@
-use std::env;
+mod cli;
+mod debugger;
+mod font;
+mod frontend;
+mod keymap;
+mod launcher;
+mod osd;
+mod speed;
+
+use chip8_core::{
+    bench, disassemble, sha1_hex, Emulator, Quirks, TraceOutput, Tracer, SCREEN_WIDTH,
+};
+use cli::{Command, Options, TraceDestination};
+use frontend::{Exit, Frontend, FrontendOptions};
+use keymap::Keymap;
+use launcher::{Launcher, RecentRoms};
+use std::{env, fs, path::Path, time};
+
+const WINDOW_TITLE: &str = "Chip-8 Emulator";
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let file_path = &args[1];
-    let mut emu = chip8_core::Emulator::new();
-    let mut canvas = emu.start_game(String::from(file_path));
-    emu.game_loop(&mut canvas);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = cli::parse(&args).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        eprintln!("Run `desktop --help` for usage");
+        std::process::exit(2);
+    });
+
+    let result = match options.command {
+        Command::Help => {
+            print!("{}", cli::HELP);
+            Ok(())
+        }
+        Command::Run => run(&options),
+        Command::Browse => browse(&options),
+        Command::Disasm => disasm(&options),
+        Command::Info => info(&options),
+        Command::Bench => bench(&options),
+        Command::Test => test(&options),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn rom_path(options: &Options) -> &str {
+    options.rom.as_deref().unwrap_or_default()
+}
+
+fn read_rom(options: &Options) -> Result<Vec<u8>, String> {
+    let path = rom_path(options);
+    fs::read(path).map_err(|e| format!("could not read ROM '{}': {}", path, e))
+}
+
+// Loads the ROM and applies the ROM database settings, then the command line overrides
+fn create_emulator(path: &str, options: &Options) -> Result<Emulator, String> {
+    let mut emu = Emulator::new();
+    emu.start_game(String::from(path))
+        .map_err(|e| format!("could not load ROM '{}': {}", path, e))?;
+
+    if let Some(quirks) = options.quirks {
+        emu.set_quirks(quirks);
+    }
+    if let Some(stack_depth) = options.stack_depth {
+        emu.set_quirks(Quirks {
+            stack_depth,
+            ..emu.quirks()
+        });
+    }
+    if let Some(instructions_per_frame) = options.instructions_per_frame {
+        emu.set_instructions_per_frame(instructions_per_frame);
+    }
+    emu.set_memory_map(options.memory_map);
+    emu.set_timing(options.timing);
+    emu.set_engine(options.engine);
+    if let Some(seed) = options.seed {
+        emu.set_seed(seed);
+    }
+    if let Some(palette) = options.palette {
+        emu.set_palette(palette);
+    }
+
+    let tracer = match &options.trace {
+        Some(TraceDestination::File(path)) => Some(
+            Tracer::to_file(
+                Path::new(path),
+                options.trace_format,
+                options.trace_filter.clone(),
+            )
+            .map_err(|e| format!("could not create trace file '{}': {}", path, e))?,
+        ),
+        Some(TraceDestination::RingBuffer(capacity)) => Some(Tracer::new(
+            TraceOutput::RingBuffer(*capacity),
+            options.trace_format,
+            options.trace_filter.clone(),
+        )),
+        None => None,
+    };
+    emu.set_tracer(tracer);
+    Ok(emu)
+}
+
+fn frontend_options(options: &Options, launcher: bool) -> Result<FrontendOptions, String> {
+    let keymap = match &options.keymap {
+        Some(path) => Keymap::load(path)?,
+        None => Keymap::default(),
+    };
+    Ok(FrontendOptions {
+        title: String::from(WINDOW_TITLE),
+        scale: options.scale,
+        keymap,
+        mute: options.mute,
+        debugger: options.debugger,
+        screenshot_scale: options.screenshot_scale,
+        recording: options.recording,
+        launcher,
+        fast_forward: options.fast_forward,
+        stats: options.stats,
+    })
+}
+
+// Prints the ROM database entry and returns the window title for the game
+fn announce(emu: &Emulator) -> String {
+    match emu.rom_info() {
+        Some(info) => {
+            println!(
+                "{} by {} ({}) - keys: {}",
+                info.title,
+                info.author,
+                info.platform.name(),
+                info.key_hints
+            );
+            format!("{} - {}", WINDOW_TITLE, info.title)
+        }
+        None => String::from(WINDOW_TITLE),
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    if Path::new(rom_path(options)).is_dir() {
+        return browse(options);
+    }
+
+    let mut emu = create_emulator(rom_path(options), options)?;
+    let mut frontend = Frontend::new(frontend_options(options, false)?)?;
+    frontend.set_title(&announce(&emu));
+    frontend.play(&mut emu)?;
+    Ok(())
+}
+
+// Shows the ROM launcher for a directory, and comes back to it when a game is left with Escape
+fn browse(options: &Options) -> Result<(), String> {
+    let dir = Path::new(rom_path(options));
+    let mut recent = RecentRoms::load();
+    let mut frontend = Frontend::new(frontend_options(options, true)?)?;
+
+    loop {
+        let mut launcher = Launcher::new(dir, &recent)
+            .map_err(|e| format!("could not list ROMs in '{}': {}", dir.display(), e))?;
+        frontend.set_title(WINDOW_TITLE);
+        let Some(path) = frontend.choose_rom(&mut launcher) else {
+            return Ok(());
+        };
+
+        let path = path.to_string_lossy().into_owned();
+        let mut emu = match create_emulator(&path, options) {
+            Ok(emu) => emu,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                continue;
+            }
+        };
+        recent.add(Path::new(&path));
+        frontend.set_title(&announce(&emu));
+        match frontend.play(&mut emu) {
+            Ok(Exit::Quit) => return Ok(()),
+            Ok(Exit::Launcher) => {}
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+}
+
+fn disasm(options: &Options) -> Result<(), String> {
+    for (address, opcode, instruction) in disassemble(&read_rom(options)?) {
+        println!("{:03X}: {:04X}  {}", address, opcode, instruction);
+    }
+    Ok(())
+}
+
+fn info(options: &Options) -> Result<(), String> {
+    let rom = read_rom(options)?;
+    println!("File:      {}", rom_path(options));
+    println!("Size:      {} bytes", rom.len());
+    println!("SHA-1:     {}", sha1_hex(&rom));
+    match chip8_core::romdb::lookup(&rom) {
+        Some(info) => {
+            println!("Title:     {}", info.title);
+            println!("Author:    {}", info.author);
+            println!("Platform:  {}", info.platform.name());
+            println!(
+                "Speed:     {} instructions per frame",
+                info.instructions_per_frame
+            );
+            println!("Keys:      {}", info.key_hints);
+            println!("Quirks:    {:?}", info.quirks);
+        }
+        None => println!("Not in the ROM database"),
+    }
+    Ok(())
+}
+
+// Runs a ROM, or every ROM in a directory, headless with a fixed seed and key script and
+// reports the speed and where the time went
+fn bench(options: &Options) -> Result<(), String> {
+    let path = Path::new(rom_path(options));
+    let roms = if path.is_dir() {
+        launcher::scan(path)
+            .map_err(|e| format!("could not list ROMs in '{}': {}", path.display(), e))?
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let seed = options.seed.unwrap_or(bench::SEED);
+    println!("{} frames, seed {}", options.frames, seed);
+    println!(
+        "{:<20} {:>13} {:>12} {:>10} {:>7} {:>8} {:>7}",
+        "ROM", "instructions", "instr/s", "frames/s", "decode", "execute", "render"
+    );
+    let mut failed = 0;
+    for rom in &roms {
+        let name = rom.file_name().unwrap_or_default().to_string_lossy();
+        let report = create_emulator(&rom.to_string_lossy(), options).and_then(|mut emu| {
+            bench::run(&mut emu, options.frames, seed).map_err(|e| e.to_string())
+        });
+        match report {
+            Ok(report) => {
+                let elapsed = report.elapsed().as_secs_f64();
+                let percent = |part: time::Duration| 100.0 * part.as_secs_f64() / elapsed;
+                println!(
+                    "{:<20} {:>13} {:>12.0} {:>10.0} {:>6.1}% {:>7.1}% {:>6.1}%",
+                    name,
+                    report.instructions,
+                    report.instructions_per_second(),
+                    report.frames_per_second(),
+                    percent(report.decode()),
+                    percent(report.execute()),
+                    percent(report.render)
+                );
+            }
+            Err(e) => {
+                println!("{:<20} {}", name, e);
+                failed += 1;
+            }
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} ROMs failed", failed, roms.len())),
+    }
+}
+
+// Runs the ROM headless and prints the final screen, so test ROMs can be checked without a
+// window - with --expect the screen's SHA-1 has to match
+fn test(options: &Options) -> Result<(), String> {
+    let mut emu = create_emulator(rom_path(options), options)?;
+    for _ in 0..options.frames {
+        emu.run_frame().map_err(|e| e.to_string())?;
+    }
+    emu.flush_trace().map_err(|e| e.to_string())?;
+
+    for row in emu.screen().chunks(SCREEN_WIDTH) {
+        let line: String = row
+            .iter()
+            .map(|pixel| if *pixel { '#' } else { '.' })
+            .collect();
+        println!("{}", line);
+    }
+
+    let pixels: Vec<u8> = emu.screen().iter().map(|pixel| *pixel as u8).collect();
+    let hash = sha1_hex(&pixels);
+    println!("Screen SHA-1: {}", hash);
+    match &options.expect {
+        Some(expected) if !expected.eq_ignore_ascii_case(&hash) => {
+            Err(format!("screen does not match, expected {}", expected))
+        }
+        _ => Ok(()),
+    }
 }
@@ -0,0 +1,183 @@
+// On-screen display - notifications, the speed status and a statistics panel drawn over the
+// scaled CHIP-8 screen
+// Only the window is drawn on, the emulator is never touched
+
+use crate::font;
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::{BlendMode, Canvas},
+    video::Window,
+};
+use std::time;
+
+// How long a notification stays on screen
+const MESSAGE_DURATION: time::Duration = time::Duration::from_secs(2);
+
+// Older notifications are dropped beyond this many
+const MAX_MESSAGES: usize = 4;
+
+const TEXT: Color = Color::RGB(255, 255, 255);
+const BACKDROP: Color = Color::RGBA(0, 0, 0, 160);
+
+// Frame rate, speed and frame time, averaged over one second windows
+#[derive(Default)]
+struct Stats {
+    fps: f64,
+    ips: f64,
+    frame_time_ms: f64,
+    sound_active: bool,
+
+    window_start: Option<time::Instant>,
+    frames: u32,
+    start_count: u64,
+    busy: time::Duration,
+}
+
+impl Stats {
+    fn record_frame(
+        &mut self,
+        instruction_count: u64,
+        frame_time: time::Duration,
+        sound_active: bool,
+    ) {
+        self.sound_active = sound_active;
+        let Some(window_start) = self.window_start else {
+            self.window_start = Some(time::Instant::now());
+            self.start_count = instruction_count;
+            return;
+        };
+
+        self.frames += 1;
+        self.busy += frame_time;
+        let elapsed = window_start.elapsed();
+        if elapsed >= time::Duration::from_secs(1) {
+            let seconds = elapsed.as_secs_f64();
+            // The count goes back to zero when the game is reset
+            let executed = instruction_count.saturating_sub(self.start_count);
+            self.fps = self.frames as f64 / seconds;
+            self.ips = executed as f64 / seconds;
+            self.frame_time_ms = self.busy.as_secs_f64() * 1000.0 / self.frames as f64;
+
+            self.window_start = Some(time::Instant::now());
+            self.start_count = instruction_count;
+            self.frames = 0;
+            self.busy = time::Duration::ZERO;
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        vec![
+            format!("FPS   {:.0}", self.fps),
+            format!("IPS   {:.0}", self.ips),
+            format!("Frame {:.2} ms", self.frame_time_ms),
+            format!("Sound {}", if self.sound_active { "on" } else { "off" }),
+        ]
+    }
+}
+
+#[derive(Default)]
+pub struct Osd {
+    // Notifications and when they were shown, oldest first
+    messages: Vec<(String, time::Instant)>,
+
+    // Shown until it is cleared, e.g. the speed while paused or fast-forwarding
+    status: Option<String>,
+
+    stats: Stats,
+    stats_visible: bool,
+}
+
+impl Osd {
+    pub fn new(stats_visible: bool) -> Self {
+        Self {
+            stats_visible,
+            ..Default::default()
+        }
+    }
+
+    // Shows a notification for a couple of seconds
+    pub fn show(&mut self, message: impl Into<String>) {
+        self.messages.push((message.into(), time::Instant::now()));
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+    }
+
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    pub fn toggle_stats(&mut self) {
+        self.stats_visible = !self.stats_visible;
+    }
+
+    // Called once per drawn frame - `frame_time` is the time spent emulating and drawing it
+    pub fn record_frame(
+        &mut self,
+        instruction_count: u64,
+        frame_time: time::Duration,
+        sound_active: bool,
+    ) {
+        self.stats
+            .record_frame(instruction_count, frame_time, sound_active);
+    }
+
+    pub fn ips(&self) -> f64 {
+        self.stats.ips
+    }
+
+    // Whether there is anything to draw, dropping notifications that have expired
+    pub fn is_visible(&mut self) -> bool {
+        self.messages
+            .retain(|(_, shown)| shown.elapsed() < MESSAGE_DURATION);
+        self.status.is_some() || !self.messages.is_empty() || self.stats_visible
+    }
+
+    // Draws the status and notifications in the top left corner and the statistics in the
+    // top right corner
+    pub fn draw(&self, canvas: &mut Canvas<Window>) {
+        let (width, height) = canvas.output_size().unwrap_or((0, 0));
+        let scale = (height / 160).max(1);
+
+        let mut left: Vec<String> = self.status.iter().cloned().collect();
+        left.extend(self.messages.iter().map(|(message, _)| message.clone()));
+        if !left.is_empty() {
+            draw_panel(canvas, &left, 0, scale);
+        }
+
+        if self.stats_visible {
+            let lines = self.stats.lines();
+            let panel_width = panel_size(&lines, scale).0;
+            draw_panel(canvas, &lines, width as i32 - panel_width as i32, scale);
+        }
+    }
+}
+
+fn panel_size(lines: &[String], scale: u32) -> (u32, u32) {
+    let margin = 2 * scale;
+    let widest = lines
+        .iter()
+        .map(|line| font::text_width(line, scale))
+        .max()
+        .unwrap_or(0);
+    (
+        widest + 2 * margin,
+        lines.len() as u32 * font::LINE_HEIGHT * scale + margin,
+    )
+}
+
+// Draws lines of text on a translucent backdrop with its top left corner at (x, 0)
+fn draw_panel(canvas: &mut Canvas<Window>, lines: &[String], x: i32, scale: u32) {
+    let margin = (2 * scale) as i32;
+    let (width, height) = panel_size(lines, scale);
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(BACKDROP);
+    canvas.fill_rect(Rect::new(x, 0, width, height)).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+
+    for (row, line) in lines.iter().enumerate() {
+        let y = margin + (row as u32 * font::LINE_HEIGHT * scale) as i32;
+        font::draw_text(canvas, line, x + margin, y, scale, TEXT);
+    }
+}
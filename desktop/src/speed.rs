@@ -0,0 +1,77 @@
+// Pause, fast-forward and slow motion
+
+use std::time;
+
+// Slow motion runs one emulated frame for this many real frames
+const SLOW_MOTION_FACTOR: u32 = 4;
+
+pub struct SpeedControl {
+    paused: bool,
+    fast_forward: bool,
+    slow_motion: bool,
+
+    // Emulated frames per real frame while fast-forwarding
+    fast_forward_multiplier: u32,
+}
+
+impl SpeedControl {
+    pub fn new(fast_forward_multiplier: u32) -> Self {
+        Self {
+            paused: false,
+            fast_forward: false,
+            slow_motion: false,
+            fast_forward_multiplier: fast_forward_multiplier.max(1),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // Fast-forward lasts while its key is held
+    pub fn set_fast_forward(&mut self, held: bool) {
+        self.fast_forward = held;
+    }
+
+    pub fn toggle_slow_motion(&mut self) {
+        self.slow_motion = !self.slow_motion;
+    }
+
+    // Emulated frames to run before the screen is drawn - only the last one is rendered
+    pub fn frames_per_tick(&self) -> u32 {
+        if self.paused {
+            0
+        } else if self.fast_forward {
+            self.fast_forward_multiplier
+        } else {
+            1
+        }
+    }
+
+    // Real time between drawn frames
+    pub fn tick_duration(&self) -> time::Duration {
+        let frame = time::Duration::from_secs(1) / chip8_core::FRAMES_PER_SECOND;
+        if self.slow_motion && !self.fast_forward {
+            frame * SLOW_MOTION_FACTOR
+        } else {
+            frame
+        }
+    }
+
+    // Text for the overlay, None at normal speed
+    pub fn label(&self) -> Option<String> {
+        if self.paused {
+            Some(String::from("Paused"))
+        } else if self.fast_forward {
+            Some(format!(">> {}x", self.fast_forward_multiplier))
+        } else if self.slow_motion {
+            Some(format!("Slow 1/{}", SLOW_MOTION_FACTOR))
+        } else {
+            None
+        }
+    }
+}
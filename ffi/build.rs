@@ -0,0 +1,138 @@
+// Generates chip8.h in OUT_DIR from the exported functions and constants in src/lib.rs
+// The copy in include/ is checked in, and a test fails when it no longer matches
+// Only the few Rust types the interface uses are understood - anything else fails the build,
+// so the header cannot silently fall out of step
+
+use std::{env, fs, path::Path};
+
+fn c_type(rust: &str) -> &'static str {
+    match rust.trim() {
+        "bool" => "bool",
+        "u8" => "uint8_t",
+        "u16" => "uint16_t",
+        "u32" => "uint32_t",
+        "u64" => "uint64_t",
+        "i32" => "int",
+        "usize" => "size_t",
+        "*const u8" => "const uint8_t *",
+        "*mut u8" => "uint8_t *",
+        "*const Emulator" => "const Chip8 *",
+        "*mut Emulator" => "Chip8 *",
+        other => panic!("no C type for `{}` in src/lib.rs", other),
+    }
+}
+
+// `type name`, without a space after a pointer's star
+fn declaration(rust_type: &str, name: &str) -> String {
+    let c = c_type(rust_type);
+    if c.ends_with('*') {
+        format!("{}{}", c, name)
+    } else {
+        format!("{} {}", c, name)
+    }
+}
+
+// `pub unsafe extern "C" fn name(a: A, b: B) -> R {` to `R name(A a, B b);`
+fn function(signature: &str) -> String {
+    let signature = signature.split_once("fn ").unwrap().1;
+    let (name, rest) = signature.split_once('(').unwrap();
+    let (parameters, rest) = rest.rsplit_once(')').unwrap();
+    let parameters: Vec<String> = parameters
+        .split(',')
+        .map(str::trim)
+        .filter(|parameter| !parameter.is_empty())
+        .map(|parameter| {
+            let (name, rust_type) = parameter.split_once(':').unwrap();
+            declaration(rust_type, name.trim())
+        })
+        .collect();
+    let parameters = if parameters.is_empty() {
+        String::from("void")
+    } else {
+        parameters.join(", ")
+    };
+    let call = format!("{}({});", name.trim(), parameters);
+    match rest.trim().trim_end_matches('{').trim().strip_prefix("->") {
+        Some(returned) => declaration(returned, &call),
+        None => format!("void {}", call),
+    }
+}
+
+fn header(source: &str) -> String {
+    let mut header = String::from(
+        "// Generated by build.rs from src/lib.rs - do not edit\n\
+         #ifndef CHIP8_H\n\
+         #define CHIP8_H\n\
+         \n\
+         #include <stdbool.h>\n\
+         #include <stddef.h>\n\
+         #include <stdint.h>\n\
+         \n\
+         #ifdef __cplusplus\n\
+         extern \"C\" {\n\
+         #endif\n\
+         \n\
+         // An emulator, made by chip8_create and freed by chip8_destroy\n\
+         typedef struct Chip8 Chip8;\n",
+    );
+
+    let mut comments = Vec::new();
+    let mut previous_define = false;
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        if line.starts_with("//") {
+            comments.push(line);
+            continue;
+        }
+        let item = if let Some(constant) = line.strip_prefix("pub const ") {
+            let (name, value) = constant.split_once(':').unwrap();
+            let value = value
+                .split_once('=')
+                .unwrap()
+                .1
+                .trim()
+                .trim_end_matches(';');
+            Some(format!("#define {} {}", name, value))
+        } else if line.starts_with("pub extern \"C\" fn")
+            || line.starts_with("pub unsafe extern \"C\" fn")
+        {
+            let mut signature = String::from(line);
+            while !signature.ends_with('{') {
+                signature.push_str(lines.next().unwrap().trim());
+            }
+            Some(function(&signature))
+        } else {
+            None
+        };
+
+        match item {
+            Some(item) => {
+                // Constants in a run without comments between them stay together
+                let define = item.starts_with("#define");
+                if !(define && previous_define && comments.is_empty()) {
+                    header.push('\n');
+                }
+                previous_define = define;
+                for comment in comments.drain(..) {
+                    header.push_str(comment);
+                    header.push('\n');
+                }
+                header.push_str(&item);
+                header.push('\n');
+            }
+            None if line.trim() == "#[no_mangle]" => {}
+            None => comments.clear(),
+        }
+    }
+
+    header.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n#endif\n");
+    header
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let source = fs::read_to_string(Path::new(&dir).join("src/lib.rs")).unwrap();
+    let out = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out).join("chip8.h"), header(&source)).unwrap();
+}
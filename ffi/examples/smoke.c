@@ -0,0 +1,130 @@
+// Drives the library through chip8.h - loads a small ROM, runs it, and checks the screen,
+// registers, memory and saved states. Exits with 1 if any check fails
+// tests/smoke.rs builds and runs it as part of `cargo test`, or by hand:
+//   cargo build
+//   cc -std=c99 -Wall -Wextra examples/smoke.c -Iinclude -Ltarget/debug -lchip8 -o target/smoke
+//   LD_LIBRARY_PATH=target/debug target/smoke
+
+#include <stdio.h>
+#include <string.h>
+
+#include "chip8.h"
+
+static int failures = 0;
+
+#define CHECK(condition)                                                      \
+    do {                                                                      \
+        if (!(condition)) {                                                   \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #condition);                                              \
+            failures++;                                                       \
+        }                                                                     \
+    } while (0)
+
+static uint8_t pixel(const Chip8 *chip8, int x, int y) {
+    return chip8_framebuffer(chip8)[y * CHIP8_SCREEN_WIDTH + x];
+}
+
+int main(void) {
+    // Draws the font's 5 at (10, 8), adds 1 to VA and then loops forever
+    static const uint8_t rom[] = {
+        0x6A, 0x05, // VA = 5
+        0xFA, 0x29, // I = sprite for VA
+        0x60, 0x0A, // V0 = 10
+        0x61, 0x08, // V1 = 8
+        0xD0, 0x15, // draw 5 rows at (V0, V1)
+        0x7A, 0x01, // VA += 1
+        0x12, 0x0C, // jump to itself
+    };
+    static uint8_t state[CHIP8_STATE_SIZE];
+    static uint8_t too_big[CHIP8_RAM_SIZE];
+
+    Chip8 *chip8 = chip8_create();
+    CHECK(chip8 != NULL);
+    CHECK(chip8_load_rom(chip8, too_big, sizeof too_big) == CHIP8_ROM_TOO_BIG);
+    CHECK(chip8_load_rom(chip8, rom, sizeof rom) == CHIP8_OK);
+    CHECK(chip8_get_pc(chip8) == 0x200);
+
+    // Stepping runs one instruction at a time
+    CHECK(chip8_step(chip8) == CHIP8_OK);
+    CHECK(chip8_get_v(chip8, 0xA) == 5);
+    CHECK(chip8_get_pc(chip8) == 0x202);
+
+    CHECK(chip8_run_frame(chip8) == CHIP8_OK);
+    CHECK(chip8_get_v(chip8, 0xA) == 6);
+    CHECK(chip8_get_pc(chip8) == 0x20C);
+    // The top row of the 5 is four lit pixels
+    CHECK(pixel(chip8, 10, 8) == 1);
+    CHECK(pixel(chip8, 13, 8) == 1);
+    CHECK(pixel(chip8, 14, 8) == 0);
+    CHECK(pixel(chip8, 0, 0) == 0);
+    CHECK(!chip8_sound_active(chip8));
+
+    // Registers
+    chip8_set_v(chip8, 3, 0x42);
+    CHECK(chip8_get_v(chip8, 3) == 0x42);
+    CHECK(chip8_get_v(chip8, 16) == 0);
+    chip8_set_i(chip8, 0x300);
+    CHECK(chip8_get_i(chip8) == 0x300);
+    chip8_set_sound_timer(chip8, 2);
+    CHECK(chip8_sound_active(chip8));
+    chip8_set_delay_timer(chip8, 9);
+    CHECK(chip8_get_delay_timer(chip8) == 9);
+    CHECK(chip8_set_pc(chip8, 0x1000) == CHIP8_OUT_OF_RANGE);
+    CHECK(chip8_set_pc(chip8, 0xFFF) == CHIP8_OUT_OF_RANGE);
+
+    // Memory
+    uint8_t bytes[2] = {0xAB, 0xCD};
+    uint8_t read[2] = {0, 0};
+    CHECK(chip8_write_memory(chip8, 0x400, bytes, 2) == CHIP8_OK);
+    CHECK(chip8_read_memory(chip8, 0x400, read, 2) == CHIP8_OK);
+    CHECK(memcmp(bytes, read, 2) == 0);
+    CHECK(chip8_read_memory(chip8, 0x200, read, 2) == CHIP8_OK);
+    CHECK(read[0] == 0x6A && read[1] == 0x05);
+    CHECK(chip8_write_memory(chip8, 0xFFF, bytes, 2) == CHIP8_OUT_OF_RANGE);
+    CHECK(chip8_read_memory(chip8, 0xFFF, read, 2) == CHIP8_OUT_OF_RANGE);
+
+    // A saved state brings everything back
+    chip8_save_state(chip8, state);
+    chip8_set_v(chip8, 3, 0);
+    chip8_set_i(chip8, 0x208);
+    chip8_write_memory(chip8, 0x400, read, 2);
+    CHECK(chip8_set_pc(chip8, 0x208) == CHIP8_OK);
+    CHECK(chip8_step(chip8) == CHIP8_OK); // draws over the 5
+    CHECK(pixel(chip8, 10, 8) == 0);
+    CHECK(chip8_load_state(chip8, state, sizeof state) == CHIP8_OK);
+    CHECK(chip8_get_v(chip8, 3) == 0x42);
+    CHECK(chip8_get_i(chip8) == 0x300);
+    CHECK(chip8_get_pc(chip8) == 0x20C);
+    CHECK(chip8_get_delay_timer(chip8) == 9);
+    CHECK(pixel(chip8, 10, 8) == 1);
+    CHECK(chip8_read_memory(chip8, 0x400, read, 2) == CHIP8_OK);
+    CHECK(read[0] == 0xAB && read[1] == 0xCD);
+    CHECK(chip8_load_state(chip8, state, sizeof state - 1) == CHIP8_BAD_STATE);
+    state[0] = 'X';
+    CHECK(chip8_load_state(chip8, state, sizeof state) == CHIP8_BAD_STATE);
+    CHECK(chip8_get_pc(chip8) == 0x20C);
+
+    // Faults come back as error codes and leave PC on the faulting instruction
+    uint8_t ret[2] = {0x00, 0xEE};
+    chip8_write_memory(chip8, 0x20C, ret, 2);
+    CHECK(chip8_step(chip8) == CHIP8_STACK_UNDERFLOW);
+    CHECK(chip8_get_pc(chip8) == 0x20C);
+    CHECK(chip8_run_frame(chip8) == CHIP8_STACK_UNDERFLOW);
+    // Storing V0 to V2 from FFE would run past the end of RAM
+    uint8_t store[2] = {0xF2, 0x55};
+    chip8_write_memory(chip8, 0x20C, store, 2);
+    chip8_set_i(chip8, 0xFFE);
+    CHECK(chip8_step(chip8) == CHIP8_OUT_OF_RANGE);
+    CHECK(chip8_get_pc(chip8) == 0x20C);
+
+    chip8_destroy(chip8);
+    chip8_destroy(NULL);
+
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("all checks passed\n");
+    return 0;
+}
@@ -0,0 +1,107 @@
+// Generated by build.rs from src/lib.rs - do not edit
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// An emulator, made by chip8_create and freed by chip8_destroy
+typedef struct Chip8 Chip8;
+
+// Results of the functions returning an int
+#define CHIP8_OK 0
+#define CHIP8_STACK_OVERFLOW 1
+#define CHIP8_STACK_UNDERFLOW 2
+#define CHIP8_ROM_TOO_BIG 3
+#define CHIP8_OUT_OF_RANGE 4
+#define CHIP8_BAD_STATE 5
+#define CHIP8_UNKNOWN_OPCODE 6
+
+// A bug in the emulator, not in the game - it may be left part way through the call
+#define CHIP8_INTERNAL_ERROR 7
+
+// Sizes in pixels and bytes
+#define CHIP8_SCREEN_WIDTH 64
+#define CHIP8_SCREEN_HEIGHT 32
+#define CHIP8_RAM_SIZE 4096
+
+// Bytes written by `chip8_save_state`
+#define CHIP8_STATE_SIZE 4539
+
+// A new emulator with the fonts loaded and no ROM, or NULL if it could not be created
+Chip8 *chip8_create(void);
+
+// Does nothing if `chip8` is NULL
+void chip8_destroy(Chip8 *chip8);
+
+// Loads a ROM and restarts the machine with it, using the ROM database's quirks and speed if
+// it is a known game
+int chip8_load_rom(Chip8 *chip8, const uint8_t *rom, size_t len);
+
+// Makes the random number instruction give the same numbers every run
+void chip8_set_seed(Chip8 *chip8, uint64_t seed);
+
+// Runs one instruction, even while the game waits for a key
+int chip8_step(Chip8 *chip8);
+
+// Runs one 60 Hz frame and ticks the timers
+int chip8_run_frame(Chip8 *chip8);
+
+// Keys are 0x0 to 0xF, anything else is ignored
+void chip8_set_key(Chip8 *chip8, uint8_t key, bool pressed);
+
+// CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT bytes, row by row, 1 for a lit pixel and 0 for an
+// unlit one - valid until the next call that changes the emulator
+const uint8_t *chip8_framebuffer(const Chip8 *chip8);
+
+bool chip8_sound_active(const Chip8 *chip8);
+
+// VX for `x` 0x0 to 0xF, 0 for anything else
+uint8_t chip8_get_v(const Chip8 *chip8, uint8_t x);
+
+// Ignored for anything but 0x0 to 0xF
+void chip8_set_v(Chip8 *chip8, uint8_t x, uint8_t value);
+
+uint16_t chip8_get_i(const Chip8 *chip8);
+
+void chip8_set_i(Chip8 *chip8, uint16_t value);
+
+uint16_t chip8_get_pc(const Chip8 *chip8);
+
+// Fails with CHIP8_OUT_OF_RANGE unless a whole instruction fits before the end of RAM
+int chip8_set_pc(Chip8 *chip8, uint16_t pc);
+
+uint8_t chip8_get_delay_timer(const Chip8 *chip8);
+
+void chip8_set_delay_timer(Chip8 *chip8, uint8_t value);
+
+uint8_t chip8_get_sound_timer(const Chip8 *chip8);
+
+void chip8_set_sound_timer(Chip8 *chip8, uint8_t value);
+
+// Copies `len` bytes of RAM from `address` into `out` - fails with CHIP8_OUT_OF_RANGE if they
+// run past the end of RAM
+int chip8_read_memory(const Chip8 *chip8, uint16_t address, uint8_t *out, size_t len);
+
+// Copies `len` bytes from `data` into RAM at `address` - fails with CHIP8_OUT_OF_RANGE if they
+// would run past the end of RAM
+int chip8_write_memory(Chip8 *chip8, uint16_t address, const uint8_t *data, size_t len);
+
+// Writes CHIP8_STATE_SIZE bytes to `out` - registers, stack, timers, keys, RAM and screen,
+// but not settings like quirks or the random number generator
+void chip8_save_state(const Chip8 *chip8, uint8_t *out);
+
+// Restores a state from `chip8_save_state` - fails with CHIP8_BAD_STATE, changing nothing, if
+// `len` is not CHIP8_STATE_SIZE or the state is not valid
+int chip8_load_state(Chip8 *chip8, const uint8_t *state, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,299 @@
+// C interface to the emulator, built as a shared library (libchip8.so, chip8.dll, ...)
+// The emulator is handed out as an opaque `Chip8 *` from `chip8_create` and given back with
+// `chip8_destroy`. Every function taking a handle needs one from `chip8_create` that has not
+// been destroyed, and pointers to buffers must be valid for the length passed with them
+// A panic never unwinds into C - functions returning an int fail with CHIP8_INTERNAL_ERROR,
+// and the others give NULL, false or 0
+// include/chip8.h is generated from this file by build.rs - comments directly above an
+// exported function or constant are copied into it. After changing either, copy the
+// generated header over the checked-in one, the test below says where it is
+
+// Safety requirements are given in the comments above
+#![allow(clippy::missing_safety_doc)]
+
+use chip8_core::{
+    Emulator, EmulatorError, NUM_KEYS, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, STATE_SIZE,
+};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+// Results of the functions returning an int
+pub const CHIP8_OK: i32 = 0;
+pub const CHIP8_STACK_OVERFLOW: i32 = 1;
+pub const CHIP8_STACK_UNDERFLOW: i32 = 2;
+pub const CHIP8_ROM_TOO_BIG: i32 = 3;
+pub const CHIP8_OUT_OF_RANGE: i32 = 4;
+pub const CHIP8_BAD_STATE: i32 = 5;
+pub const CHIP8_UNKNOWN_OPCODE: i32 = 6;
+// A bug in the emulator, not in the game - it may be left part way through the call
+pub const CHIP8_INTERNAL_ERROR: i32 = 7;
+
+// Sizes in pixels and bytes
+pub const CHIP8_SCREEN_WIDTH: usize = 64;
+pub const CHIP8_SCREEN_HEIGHT: usize = 32;
+pub const CHIP8_RAM_SIZE: usize = 4096;
+// Bytes written by `chip8_save_state`
+pub const CHIP8_STATE_SIZE: usize = 4539;
+
+// The header gets the numbers written out, so check they match the emulator's
+const _: () = assert!(CHIP8_SCREEN_WIDTH == SCREEN_WIDTH);
+const _: () = assert!(CHIP8_SCREEN_HEIGHT == SCREEN_HEIGHT);
+const _: () = assert!(CHIP8_RAM_SIZE == RAM_SIZE);
+const _: () = assert!(CHIP8_STATE_SIZE == STATE_SIZE);
+
+fn result(result: Result<(), EmulatorError>) -> i32 {
+    match result {
+        Ok(()) => CHIP8_OK,
+        Err(EmulatorError::StackOverflow { .. }) => CHIP8_STACK_OVERFLOW,
+        Err(EmulatorError::StackUnderflow { .. }) => CHIP8_STACK_UNDERFLOW,
+        Err(EmulatorError::UnknownOpcode { .. }) => CHIP8_UNKNOWN_OPCODE,
+        Err(EmulatorError::MemoryOutOfRange { .. }) => CHIP8_OUT_OF_RANGE,
+        Err(EmulatorError::Panicked { .. }) => CHIP8_INTERNAL_ERROR,
+    }
+}
+
+// Runs the body of an exported function, giving `on_panic` instead of unwinding into C if the
+// emulator panics. Errors a game can cause come back as results, so this only catches bugs
+fn guard<T>(on_panic: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(on_panic)
+}
+
+// A new emulator with the fonts loaded and no ROM, or NULL if it could not be created
+#[no_mangle]
+pub extern "C" fn chip8_create() -> *mut Emulator {
+    guard(ptr::null_mut(), || {
+        let mut emu = Emulator::new();
+        emu.load_fonts();
+        Box::into_raw(Box::new(emu))
+    })
+}
+
+// Does nothing if `chip8` is NULL
+#[no_mangle]
+pub unsafe extern "C" fn chip8_destroy(chip8: *mut Emulator) {
+    guard((), || {
+        if !chip8.is_null() {
+            drop(Box::from_raw(chip8));
+        }
+    })
+}
+
+// Loads a ROM and restarts the machine with it, using the ROM database's quirks and speed if
+// it is a known game
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Emulator, rom: *const u8, len: usize) -> i32 {
+    guard(CHIP8_INTERNAL_ERROR, || {
+        let emu = &mut *chip8;
+        if emu.load_rom_bytes(slice::from_raw_parts(rom, len)).is_err() {
+            return CHIP8_ROM_TOO_BIG;
+        }
+        if let Some(info) = emu.rom_info() {
+            emu.apply_rom_info(info);
+        }
+        emu.reset();
+        CHIP8_OK
+    })
+}
+
+// Makes the random number instruction give the same numbers every run
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_seed(chip8: *mut Emulator, seed: u64) {
+    guard((), || (*chip8).set_seed(seed))
+}
+
+// Runs one instruction, even while the game waits for a key
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Emulator) -> i32 {
+    guard(CHIP8_INTERNAL_ERROR, || result((*chip8).step()))
+}
+
+// Runs one 60 Hz frame and ticks the timers
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip8: *mut Emulator) -> i32 {
+    guard(CHIP8_INTERNAL_ERROR, || result((*chip8).run_frame()))
+}
+
+// Keys are 0x0 to 0xF, anything else is ignored
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Emulator, key: u8, pressed: bool) {
+    guard((), || {
+        if (key as usize) < NUM_KEYS {
+            (*chip8).set_key(key as usize, pressed);
+        }
+    })
+}
+
+// CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT bytes, row by row, 1 for a lit pixel and 0 for an
+// unlit one - valid until the next call that changes the emulator
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const Emulator) -> *const u8 {
+    guard(ptr::null(), || (*chip8).screen().as_ptr() as *const u8)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound_active(chip8: *const Emulator) -> bool {
+    guard(false, || (*chip8).sound_active())
+}
+
+// VX for `x` 0x0 to 0xF, 0 for anything else
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_v(chip8: *const Emulator, x: u8) -> u8 {
+    guard(0, || {
+        (*chip8).v_registers().get(x as usize).copied().unwrap_or(0)
+    })
+}
+
+// Ignored for anything but 0x0 to 0xF
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_v(chip8: *mut Emulator, x: u8, value: u8) {
+    guard((), || {
+        let emu = &mut *chip8;
+        if (x as usize) < emu.v_registers().len() {
+            emu.set_v_register(x as usize, value);
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_i(chip8: *const Emulator) -> u16 {
+    guard(0, || (*chip8).i_register())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_i(chip8: *mut Emulator, value: u16) {
+    guard((), || (*chip8).set_i_register(value))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_pc(chip8: *const Emulator) -> u16 {
+    guard(0, || (*chip8).pc())
+}
+
+// Fails with CHIP8_OUT_OF_RANGE unless a whole instruction fits before the end of RAM
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_pc(chip8: *mut Emulator, pc: u16) -> i32 {
+    guard(CHIP8_INTERNAL_ERROR, || {
+        if pc as usize > RAM_SIZE - 2 {
+            return CHIP8_OUT_OF_RANGE;
+        }
+        (*chip8).set_pc(pc);
+        CHIP8_OK
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_delay_timer(chip8: *const Emulator) -> u8 {
+    guard(0, || (*chip8).delay_timer())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_delay_timer(chip8: *mut Emulator, value: u8) {
+    guard((), || (*chip8).set_delay_timer(value))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_sound_timer(chip8: *const Emulator) -> u8 {
+    guard(0, || (*chip8).sound_timer())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_sound_timer(chip8: *mut Emulator, value: u8) {
+    guard((), || (*chip8).set_sound_timer(value))
+}
+
+// Copies `len` bytes of RAM from `address` into `out` - fails with CHIP8_OUT_OF_RANGE if they
+// run past the end of RAM
+#[no_mangle]
+pub unsafe extern "C" fn chip8_read_memory(
+    chip8: *const Emulator,
+    address: u16,
+    out: *mut u8,
+    len: usize,
+) -> i32 {
+    guard(CHIP8_INTERNAL_ERROR, || {
+        let start = address as usize;
+        if len > RAM_SIZE.saturating_sub(start) {
+            return CHIP8_OUT_OF_RANGE;
+        }
+        if len > 0 {
+            slice::from_raw_parts_mut(out, len)
+                .copy_from_slice(&(*chip8).memory()[start..start + len]);
+        }
+        CHIP8_OK
+    })
+}
+
+// Copies `len` bytes from `data` into RAM at `address` - fails with CHIP8_OUT_OF_RANGE if they
+// would run past the end of RAM
+#[no_mangle]
+pub unsafe extern "C" fn chip8_write_memory(
+    chip8: *mut Emulator,
+    address: u16,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    guard(CHIP8_INTERNAL_ERROR, || {
+        let start = address as usize;
+        if len > RAM_SIZE.saturating_sub(start) {
+            return CHIP8_OUT_OF_RANGE;
+        }
+        if len > 0 {
+            (*chip8).write_memory(start, slice::from_raw_parts(data, len));
+        }
+        CHIP8_OK
+    })
+}
+
+// Writes CHIP8_STATE_SIZE bytes to `out` - registers, stack, timers, keys, RAM and screen,
+// but not settings like quirks or the random number generator
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(chip8: *const Emulator, out: *mut u8) {
+    guard((), || {
+        (*chip8).save_state(&mut *(out as *mut [u8; STATE_SIZE]));
+    })
+}
+
+// Restores a state from `chip8_save_state` - fails with CHIP8_BAD_STATE, changing nothing, if
+// `len` is not CHIP8_STATE_SIZE or the state is not valid
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(
+    chip8: *mut Emulator,
+    state: *const u8,
+    len: usize,
+) -> i32 {
+    guard(CHIP8_INTERNAL_ERROR, || {
+        if len != STATE_SIZE {
+            return CHIP8_BAD_STATE;
+        }
+        match (*chip8).load_state(&*(state as *const [u8; STATE_SIZE])) {
+            Ok(()) => CHIP8_OK,
+            Err(_) => CHIP8_BAD_STATE,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panics_become_results() {
+        assert_eq!(
+            guard(CHIP8_INTERNAL_ERROR, || panic!("bug")),
+            CHIP8_INTERNAL_ERROR
+        );
+        assert_eq!(guard(CHIP8_INTERNAL_ERROR, || CHIP8_OK), CHIP8_OK);
+    }
+
+    #[test]
+    fn checked_in_header_is_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/chip8.h"));
+        assert!(
+            generated == include_str!("../include/chip8.h"),
+            "include/chip8.h is out of date, copy {}/chip8.h over it",
+            env!("OUT_DIR")
+        );
+    }
+}
@@ -0,0 +1,34 @@
+// Compiles examples/smoke.c against the library cargo just built and runs it
+// Needs a C compiler on the PATH as `cc`
+
+use std::{env, path::Path, process::Command};
+
+#[test]
+fn c_smoke_test_passes() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // Tests run from target/<profile>/deps, next to the library
+    let library = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("smoke");
+
+    let compiled = Command::new("cc")
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror"])
+        .arg(manifest.join("examples/smoke.c"))
+        .arg("-I")
+        .arg(manifest.join("include"))
+        .arg("-L")
+        .arg(&library)
+        .arg(format!("-Wl,-rpath,{}", library.display()))
+        .arg("-lchip8")
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .expect("could not run cc");
+    assert!(compiled.success(), "examples/smoke.c did not compile");
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "examples/smoke.c failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
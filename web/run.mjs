@@ -0,0 +1,50 @@
+// Runs a ROM in the WebAssembly build under Node and prints the final screen - a quick check
+// of the module outside a browser
+//   cargo build --release --target wasm32-unknown-unknown
+//   node run.mjs ../roms/PONG [FRAMES]
+
+import { readFileSync } from "node:fs";
+
+const [romPath, frames = "600"] = process.argv.slice(2);
+if (!romPath) {
+  console.error("usage: node run.mjs <ROM> [FRAMES]");
+  process.exit(1);
+}
+
+const wasm = readFileSync(
+  new URL("target/wasm32-unknown-unknown/release/web.wasm", import.meta.url),
+);
+const { instance } = await WebAssembly.instantiate(wasm);
+const chip8 = instance.exports;
+
+const rom = readFileSync(romPath);
+const ptr = chip8.alloc(rom.length);
+new Uint8Array(chip8.memory.buffer, ptr, rom.length).set(rom);
+const loaded = chip8.load_rom(ptr, rom.length);
+chip8.dealloc(ptr, rom.length);
+if (!loaded) {
+  console.error(`${romPath} is too big to load`);
+  process.exit(1);
+}
+
+// Taps key 5 now and then, so games waiting for a key keep going
+let sounding = 0;
+for (let frame = 0; frame < Number(frames); frame++) {
+  if (frame % 30 === 0) chip8.key_down(5);
+  if (frame % 30 === 20) chip8.key_up(5);
+  if (!chip8.run_frame()) {
+    console.error(`crashed in frame ${frame}`);
+    process.exit(1);
+  }
+  sounding += chip8.sound_active();
+}
+
+const width = chip8.framebuffer_width();
+const height = chip8.framebuffer_height();
+const pixels = new Uint8Array(chip8.memory.buffer, chip8.framebuffer_ptr(), width * height);
+for (let y = 0; y < height; y++) {
+  let row = "";
+  for (let x = 0; x < width; x++) row += pixels[y * width + x] ? "#" : ".";
+  console.log(row);
+}
+console.log(`${frames} frames, sound on for ${sounding}`);
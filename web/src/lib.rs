@@ -0,0 +1,96 @@
+// WebAssembly build of the emulator for embedding in web pages
+// Everything goes through plain numbers and pointers into the module's memory, so the module
+// can be used from JavaScript without any generated glue:
+// - `alloc(len)` reserves room to copy a ROM into, `load_rom(ptr, len)` starts it and
+//   `dealloc(ptr, len)` gives the room back
+// - `run_frame()` runs one 60 Hz frame, with `key_down(key)` / `key_up(key)` in between
+// - `framebuffer_ptr()` points at `framebuffer_width() * framebuffer_height()` bytes, one per
+//   pixel row by row, 1 if lit - the pointer changes when a ROM is loaded
+// - `sound_active()` says whether the beeper should sound
+// The module has no clock or random source of its own, so CXNN gives the same numbers every
+// time a ROM is loaded
+
+// Safety requirements are given in plain comments
+#![allow(clippy::missing_safety_doc)]
+
+use chip8_core::{Emulator, NUM_KEYS, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::{cell::RefCell, mem, slice};
+
+thread_local! {
+    static EMULATOR: RefCell<Emulator> = RefCell::new(Emulator::new());
+}
+
+#[no_mangle]
+pub extern "C" fn alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    mem::forget(buffer);
+    ptr
+}
+
+// Safety: `ptr` and `len` must come from one call to `alloc`
+#[no_mangle]
+pub unsafe extern "C" fn dealloc(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+// Powers on a new machine with the ROM, using the ROM database's settings if it is a known
+// game - false if the ROM is too big, leaving the running game alone
+// Safety: `ptr` must point at `len` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn load_rom(ptr: *const u8, len: usize) -> bool {
+    let rom = slice::from_raw_parts(ptr, len);
+    let mut emu = Emulator::new();
+    emu.load_fonts();
+    if emu.load_rom_bytes(rom).is_err() {
+        return false;
+    }
+    if let Some(info) = emu.rom_info() {
+        emu.apply_rom_info(info);
+    }
+    EMULATOR.with(|emulator| *emulator.borrow_mut() = emu);
+    true
+}
+
+// False if the game crashed - it stays on the faulting instruction
+#[no_mangle]
+pub extern "C" fn run_frame() -> bool {
+    EMULATOR.with(|emulator| emulator.borrow_mut().run_frame().is_ok())
+}
+
+// Keys are 0x0 to 0xF, anything else is ignored
+#[no_mangle]
+pub extern "C" fn key_down(key: u32) {
+    set_key(key, true);
+}
+
+#[no_mangle]
+pub extern "C" fn key_up(key: u32) {
+    set_key(key, false);
+}
+
+fn set_key(key: u32, pressed: bool) {
+    if (key as usize) < NUM_KEYS {
+        EMULATOR.with(|emulator| emulator.borrow_mut().set_key(key as usize, pressed));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn framebuffer_ptr() -> *const u8 {
+    EMULATOR.with(|emulator| emulator.borrow().screen().as_ptr() as *const u8)
+}
+
+#[no_mangle]
+pub extern "C" fn framebuffer_width() -> u32 {
+    SCREEN_WIDTH as u32
+}
+
+#[no_mangle]
+pub extern "C" fn framebuffer_height() -> u32 {
+    SCREEN_HEIGHT as u32
+}
+
+#[no_mangle]
+pub extern "C" fn sound_active() -> bool {
+    EMULATOR.with(|emulator| emulator.borrow().sound_active())
+}